@@ -0,0 +1,259 @@
+//! # Transform Cache
+//!
+//! This module fingerprints datasets and caches transform results on disk, like the Hugging Face
+//! datasets cache, so re-running a preprocessing pipeline reuses every result whose input and
+//! parameters did not change. A dataset loaded from files is fingerprinted by the SHA-256 of their
+//! content, and each transform derives the fingerprint of its result from that of its input, its
+//! name and its parameters. Results are kept in `<cache root>/transforms` (see `hub::cache_root`)
+//! as an Arrow IPC file named after their fingerprint, next to a JSON file with their `info`.
+//!
+//! Transform parameters are fingerprinted by their JSON serialization, which does not change
+//! between versions the way `Debug` output may. Datasets built in memory with `DataSet::new` have
+//! no fingerprint and are never cached, and neither are `map` results without a
+//! `MapOptions::cache_key`, since a closure cannot be fingerprinted, nor `filter` results, since
+//! a Polars expression has no stable serialization. Caching is turned off with `disable_caching`
+//! or by setting `DATASETS_RS_DISABLE_CACHE`, and `clear_cache` removes all cached results.
+//! `with_cache_dir` and `without_caching` change these settings for a scope on the current thread
+//! only, so that tests running in parallel do not share them.
+
+use polars::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+use crate::csv_options::CsvOptions;
+use crate::download::{self, file_sha256};
+use crate::hub;
+use crate::load_dataset::{DataSet, DatasetInfo};
+use crate::multi_file::expand_source;
+
+/// Environment variable that, when set to anything but `0`, disables the transform cache.
+pub const DISABLE_CACHE_ENV: &str = "DATASETS_RS_DISABLE_CACHE";
+
+static CACHING_DISABLED: AtomicBool = AtomicBool::new(false);
+static CACHE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+thread_local! {
+    static SCOPED_CACHE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    static SCOPED_CACHING_DISABLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs a closure when dropped, to restore a scoped setting even if the scope panics.
+struct OnDrop<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for OnDrop<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            f();
+        }
+    }
+}
+
+/// Stop reading and writing cached transform results; fingerprints are still derived.
+pub fn disable_caching() {
+    CACHING_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Undo `disable_caching`.
+pub fn enable_caching() {
+    CACHING_DISABLED.store(false, Ordering::Relaxed);
+}
+
+/// Run `f` without reading or writing cached transform results on the current thread.
+pub fn without_caching<T, F: FnOnce() -> T>(f: F) -> T {
+    let previous = SCOPED_CACHING_DISABLED.replace(true);
+    let _restore = OnDrop(Some(move || SCOPED_CACHING_DISABLED.set(previous)));
+    f()
+}
+
+/// Whether transform results are cached, i.e. neither `disable_caching` was called, nor
+/// `DATASETS_RS_DISABLE_CACHE` is set, nor the current thread runs in `without_caching`.
+pub fn is_caching_enabled() -> bool {
+    !CACHING_DISABLED.load(Ordering::Relaxed)
+        && !SCOPED_CACHING_DISABLED.get()
+        && !env::var_os(DISABLE_CACHE_ENV).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Keep cached results in `dir` instead of `<cache root>/transforms`, or go back to it if `None`.
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.write().unwrap_or_else(PoisonError::into_inner) = dir;
+}
+
+/// Run `f` keeping cached results in `dir` on the current thread, whatever `set_cache_dir` says.
+pub fn with_cache_dir<P: AsRef<Path>, T, F: FnOnce() -> T>(dir: P, f: F) -> T {
+    let previous = SCOPED_CACHE_DIR.replace(Some(dir.as_ref().to_path_buf()));
+    let _restore = OnDrop(Some(move || {
+        SCOPED_CACHE_DIR.replace(previous);
+    }));
+    f()
+}
+
+/// The directory holding cached transform results.
+pub fn cache_dir() -> PathBuf {
+    SCOPED_CACHE_DIR
+        .with_borrow(Clone::clone)
+        .or_else(|| CACHE_DIR.read().unwrap_or_else(PoisonError::into_inner).clone())
+        .unwrap_or_else(|| hub::cache_root().join("transforms"))
+}
+
+/// Whether `file_name` is one of the cache's own files: `<fingerprint>.arrow`, its `.json` info
+/// or an unfinished `.arrow.part`.
+fn is_cache_file(file_name: &str) -> bool {
+    let fingerprint = [".arrow.part", ".arrow", ".json"].iter().find_map(|suffix| file_name.strip_suffix(suffix));
+    fingerprint.is_some_and(|fingerprint| fingerprint.len() == 64 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Remove every cached transform result, returning how many there were.
+///
+/// Only the cache's own files are removed, so other files in the cache directory are kept.
+pub fn clear_cache() -> Result<usize, Box<dyn Error>> {
+    let dir = cache_dir();
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if path.is_file() && is_cache_file(file_name) {
+            removed += usize::from(file_name.ends_with(".arrow"));
+            fs::remove_file(&path)?;
+        }
+    }
+    log::info!("Removed {} cached results from {}", removed, dir.display());
+    Ok(removed)
+}
+
+/// The hex SHA-256 of `parts`, kept apart so that moving text between parts changes it.
+fn hash_parts<S: AsRef<str>>(parts: &[S]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_ref().as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Transform parameters as canonical JSON, to derive fingerprints from.
+pub(crate) fn params_key<T: Serialize + ?Sized>(params: &T) -> String {
+    serde_json::to_string(params).expect("transform parameters serialize to JSON")
+}
+
+/// Fingerprint of the result of `transform` with `params` combining `datasets`, if they all have
+/// a fingerprint.
+pub(crate) fn combine_fingerprints(transform: &str, params: &str, datasets: &[DataSet]) -> Option<String> {
+    let mut parts = vec![transform, params];
+    for dataset in datasets {
+        parts.push(dataset.fingerprint.as_deref()?);
+    }
+    Some(hash_parts(&parts))
+}
+
+/// Fingerprint of a dataset loaded from `sources`, from the content of the files they expand to
+/// and the CSV dialect they are read with, or `None` if a source is a URL.
+pub fn source_fingerprint<S: AsRef<str>>(
+    sources: &[S],
+    csv_options: &CsvOptions,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut hashes = vec![csv_options.cache_key()];
+    for source in sources {
+        let source = source.as_ref();
+        if download::is_url(source) {
+            return Ok(None);
+        }
+        for file in expand_source(source)? {
+            hashes.push(file_sha256(&file)?);
+        }
+    }
+    Ok(Some(hash_parts(&hashes)))
+}
+
+/// Fingerprint of the content of `df`: the SHA-256 of its schema and rows as Arrow IPC.
+pub fn data_fingerprint(df: &DataFrame) -> Result<String, Box<dyn Error>> {
+    let mut df = df.clone();
+    df.as_single_chunk_par();
+    let mut hasher = Sha256::new();
+    IpcWriter::new(&mut hasher).finish(&mut df)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Read a cached result written by `write_cached`.
+fn read_cached(data_path: &Path, info_path: &Path) -> Result<(DataFrame, DatasetInfo), Box<dyn Error>> {
+    let data = IpcReader::new(File::open(data_path)?).finish()?;
+    let info = serde_json::from_reader(BufReader::new(File::open(info_path)?))?;
+    Ok((data, info))
+}
+
+/// Write `dataset` to the cache; the data file is renamed into place last, so it only exists once
+/// the entry is complete.
+fn write_cached(dataset: &DataSet, data_path: &Path, info_path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = data_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    serde_json::to_writer(BufWriter::new(File::create(info_path)?), &dataset.info)?;
+    let part = data_path.with_extension("arrow.part");
+    IpcWriter::new(BufWriter::new(File::create(&part)?)).finish(&mut dataset.data.clone())?;
+    fs::rename(&part, data_path)?;
+    Ok(())
+}
+
+impl DataSet {
+    /// The dataset's fingerprint, or the fingerprint of its data if it has none.
+    pub fn fingerprint(&self) -> Result<String, Box<dyn Error>> {
+        match &self.fingerprint {
+            Some(fingerprint) => Ok(fingerprint.clone()),
+            None => data_fingerprint(&self.data),
+        }
+    }
+
+    /// Fingerprint of the result of `transform` with `params`, if this dataset has a fingerprint.
+    pub(crate) fn derive_fingerprint(&self, transform: &str, params: &str) -> Option<String> {
+        self.fingerprint.as_ref().map(|fingerprint| hash_parts(&[fingerprint, transform, params]))
+    }
+
+    /// Apply `transform` with `params` by running `f`, or load its result from the cache.
+    ///
+    /// Results of datasets without a fingerprint are neither cached nor fingerprinted. A cache
+    /// entry that cannot be read or written is only logged, and the transform is run as usual.
+    pub(crate) fn cached<F>(&self, transform: &str, params: &str, f: F) -> Result<DataSet, Box<dyn Error>>
+    where
+        F: FnOnce() -> Result<DataSet, Box<dyn Error>>,
+    {
+        let Some(fingerprint) = self.derive_fingerprint(transform, params) else {
+            return f();
+        };
+        let dir = cache_dir();
+        let data_path = dir.join(format!("{}.arrow", fingerprint));
+        let info_path = dir.join(format!("{}.json", fingerprint));
+        if is_caching_enabled() && data_path.exists() {
+            match read_cached(&data_path, &info_path) {
+                Ok((data, info)) => {
+                    log::info!("Loaded cached {} result {}", transform, data_path.display());
+                    let mut dataset = self.with_data(data);
+                    dataset.info = info;
+                    dataset.fingerprint = Some(fingerprint);
+                    return Ok(dataset);
+                }
+                Err(e) => log::warn!("Ignoring unreadable cache entry {}: {}", data_path.display(), e),
+            }
+        }
+
+        let mut dataset = f()?;
+        dataset.fingerprint = Some(fingerprint);
+        if is_caching_enabled() {
+            if let Err(e) = write_cached(&dataset, &data_path, &info_path) {
+                log::warn!("Cannot cache {} result in {}: {}", transform, dir.display(), e);
+            }
+        }
+        Ok(dataset)
+    }
+}
@@ -0,0 +1,127 @@
+//! # Column Management
+//!
+//! This module renames, removes, selects, casts, adds and flattens the columns of a `DataSet`,
+//! like the column methods of a Hugging Face `Dataset`. Every method returns a new dataset and
+//! fails with the list of available columns when a column does not exist.
+
+use polars::prelude::*;
+use std::error::Error;
+
+use crate::cache::params_key;
+use crate::load_dataset::DataSet;
+
+/// Check that `df` has every one of `columns`.
+pub fn check_columns(df: &DataFrame, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+    for column in columns {
+        if df.get_column_index(column).is_none() {
+            let available = df.get_column_names().join(", ");
+            return Err(format!("Column {} not found; available columns: {}", column, available).into());
+        }
+    }
+    Ok(())
+}
+
+/// Parse a `name=value` pair, such as a rename, a cast or a column to add.
+pub fn parse_assignment(value: &str) -> Result<(String, String), Box<dyn Error>> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("Expected name=value, got {:?}", value).into()),
+    }
+}
+
+impl DataSet {
+    /// The dataset with new data from a column operation, fingerprinted as `transform`.
+    fn with_columns_changed(&self, data: DataFrame, transform: &str, params: &str) -> DataSet {
+        let mut dataset = self.with_data(data);
+        dataset.fingerprint = self.derive_fingerprint(transform, params);
+        dataset
+    }
+
+    /// Rename column `old` to `new`.
+    pub fn rename_column(&self, old: &str, new: &str) -> Result<DataSet, Box<dyn Error>> {
+        self.rename_columns(&[(old, new)])
+    }
+
+    /// Rename columns by `(old, new)` pairs, failing if a new name is already taken.
+    pub fn rename_columns(&self, renames: &[(&str, &str)]) -> Result<DataSet, Box<dyn Error>> {
+        let old: Vec<&str> = renames.iter().map(|(old, _)| *old).collect();
+        check_columns(&self.data, &old)?;
+        let mut data = self.data.clone();
+        let mut names: Vec<String> = data.get_column_names().iter().map(|name| name.to_string()).collect();
+        for (old, new) in renames {
+            if let Some(position) = names.iter().position(|name| name == old) {
+                names[position] = new.to_string();
+            }
+        }
+        for (position, name) in names.iter().enumerate() {
+            if names[..position].contains(name) {
+                return Err(format!("Renaming would give two columns named {}", name).into());
+            }
+        }
+        data.set_column_names(&names)?;
+        Ok(self.with_columns_changed(data, "rename_columns", &params_key(renames)))
+    }
+
+    /// Remove `columns`.
+    pub fn remove_columns(&self, columns: &[&str]) -> Result<DataSet, Box<dyn Error>> {
+        check_columns(&self.data, columns)?;
+        let data = self.data.drop_many(columns);
+        Ok(self.with_columns_changed(data, "remove_columns", &params_key(columns)))
+    }
+
+    /// Keep only `columns`, in the given order, which also reorders them.
+    pub fn select_columns(&self, columns: &[&str]) -> Result<DataSet, Box<dyn Error>> {
+        check_columns(&self.data, columns)?;
+        let data = self.data.select(columns.iter().copied())?;
+        Ok(self.with_columns_changed(data, "select_columns", &params_key(columns)))
+    }
+
+    /// Cast `column` to `dtype`; values that cannot be cast are an error rather than null.
+    pub fn cast_column(&self, column: &str, dtype: &DataType) -> Result<DataSet, Box<dyn Error>> {
+        check_columns(&self.data, &[column])?;
+        let series = self.data.column(column)?;
+        let cast = series
+            .strict_cast(dtype)
+            .map_err(|e| format!("Cannot cast column {} from {} to {}: {}", column, series.dtype(), dtype, e))?;
+        let mut data = self.data.clone();
+        data.replace(column, cast)?;
+        Ok(self.with_columns_changed(data, "cast_column", &params_key(&(column, dtype.to_string()))))
+    }
+
+    /// Append `values` as a new column named `name`, with one value per row.
+    pub fn add_column(&self, name: &str, values: Series) -> Result<DataSet, Box<dyn Error>> {
+        if self.data.get_column_index(name).is_some() {
+            return Err(format!("Column {} already exists", name).into());
+        }
+        if values.len() != self.data.height() {
+            let message = format!("Column {} has {} values for {} rows", name, values.len(), self.data.height());
+            return Err(message.into());
+        }
+        let mut data = self.data.clone();
+        data.with_column(values.with_name(name))?;
+        // The values come from outside the dataset, so the result is fingerprinted by its content.
+        Ok(self.with_data(data))
+    }
+
+    /// Replace every struct column by one column per field, named `<column>.<field>`, until no
+    /// struct columns are left, as when loading nested JSON records.
+    pub fn flatten(&self) -> Result<DataSet, Box<dyn Error>> {
+        let mut columns: Vec<Series> = self.data.get_columns().to_vec();
+        while columns.iter().any(|series| matches!(series.dtype(), DataType::Struct(_))) {
+            let mut flattened = Vec::with_capacity(columns.len());
+            for series in columns {
+                if !matches!(series.dtype(), DataType::Struct(_)) {
+                    flattened.push(series);
+                    continue;
+                }
+                for field in series.struct_()?.fields_as_series() {
+                    let name = format!("{}.{}", series.name(), field.name());
+                    flattened.push(field.with_name(&name));
+                }
+            }
+            columns = flattened;
+        }
+        let data = DataFrame::new(columns)?;
+        Ok(self.with_columns_changed(data, "flatten", ""))
+    }
+}
@@ -0,0 +1,152 @@
+//! # Compressed Files
+//!
+//! This module detects compound extensions such as `events.csv.gz` or `flows.jsonl.zst` and
+//! wraps files in streaming gzip, zstd, bzip2 or xz decoders and encoders, so the format readers
+//! and writers never see the compression.
+
+use bzip2::read::BzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+/// A compression codec applied on top of a file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// Map a file extension such as `gz` or `zst` to its codec.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// The file extension written for this codec.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Bzip2 => "bz2",
+            Compression::Xz => "xz",
+        }
+    }
+}
+
+/// Split a format string such as `csv.gz` into the format (`csv`) and its compression.
+pub fn split_format(format: &str) -> (&str, Compression) {
+    match format.rsplit_once('.') {
+        Some((inner, ext)) => match Compression::from_extension(ext) {
+            Some(compression) => (inner, compression),
+            None => (format, Compression::None),
+        },
+        None => (format, Compression::None),
+    }
+}
+
+/// Detect the format and compression of a path from its (possibly compound) extension.
+///
+/// `events.csv.gz` gives `("csv", Compression::Gzip)` and `events.csv` gives `("csv", Compression::None)`.
+pub fn path_format(path: &Path) -> (String, Compression) {
+    let ext = path.extension().and_then(std::ffi::OsStr::to_str).unwrap_or("");
+    match Compression::from_extension(ext) {
+        Some(compression) => {
+            let stem = path.file_stem().map(Path::new);
+            let inner = stem.and_then(Path::extension).and_then(std::ffi::OsStr::to_str).unwrap_or("");
+            (inner.to_string(), compression)
+        }
+        None => (ext.to_string(), Compression::None),
+    }
+}
+
+/// Open a file as a buffered stream of decompressed bytes.
+pub fn open_decompressed<P: AsRef<Path>>(
+    file_path: P,
+    compression: Compression,
+) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let file = BufReader::new(File::open(file_path)?);
+    let reader: Box<dyn BufRead> = match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?)),
+        Compression::Bzip2 => Box::new(BufReader::new(BzDecoder::new(file))),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new(file))),
+    };
+    Ok(reader)
+}
+
+/// A file writer that compresses everything written to it.
+///
+/// `finish` must be called once writing is done so the codec can write its trailer.
+pub enum CompressedWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Bzip2(BzEncoder<BufWriter<File>>),
+    Xz(XzEncoder<BufWriter<File>>),
+}
+
+impl CompressedWriter {
+    /// Create `file_path` and wrap it in an encoder for `compression`.
+    pub fn create<P: AsRef<Path>>(file_path: P, compression: Compression) -> Result<Self, Box<dyn Error>> {
+        let file = BufWriter::new(File::create(file_path)?);
+        let writer = match compression {
+            Compression::None => CompressedWriter::Plain(file),
+            Compression::Gzip => CompressedWriter::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(file, 0)?),
+            Compression::Bzip2 => CompressedWriter::Bzip2(BzEncoder::new(file, bzip2::Compression::default())),
+            Compression::Xz => CompressedWriter::Xz(XzEncoder::new(file, 6)),
+        };
+        Ok(writer)
+    }
+
+    /// Write the codec trailer and flush the file.
+    pub fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            CompressedWriter::Plain(file) => file,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+            CompressedWriter::Bzip2(encoder) => encoder.finish()?,
+            CompressedWriter::Xz(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(w) => w.write(buf),
+            CompressedWriter::Gzip(w) => w.write(buf),
+            CompressedWriter::Zstd(w) => w.write(buf),
+            CompressedWriter::Bzip2(w) => w.write(buf),
+            CompressedWriter::Xz(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(w) => w.flush(),
+            CompressedWriter::Gzip(w) => w.flush(),
+            CompressedWriter::Zstd(w) => w.flush(),
+            CompressedWriter::Bzip2(w) => w.flush(),
+            CompressedWriter::Xz(w) => w.flush(),
+        }
+    }
+}
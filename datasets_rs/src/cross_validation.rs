@@ -0,0 +1,244 @@
+//! # Cross-Validation
+//!
+//! This module yields `(train, validation)` pairs of a `DataSet` for k-fold, stratified k-fold,
+//! repeated k-fold, group k-fold and leave-one-group-out cross-validation. Folds are
+//! `DataSetView`s: row indices into the borrowed dataset, so no fold copies the `DataFrame` until
+//! it is materialized with `DataSetView::to_dataset` or turned into `ndarray`s for a linfa model
+//! with `DataSetView::to_ndarray`.
+
+use ndarray::{Array1, Array2};
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+
+use crate::load_dataset::DataSet;
+use crate::split::{class_indices, permutation, take_rows};
+
+/// A subset of the rows of a `DataSet`, by index.
+#[derive(Clone)]
+pub struct DataSetView<'a> {
+    pub(crate) dataset: &'a DataSet,
+    pub(crate) rows: Vec<IdxSize>,
+    /// Seed of the last shuffle or sample of the view, recorded in the `info` of `to_dataset`.
+    pub(crate) seed: Option<u64>,
+}
+
+impl<'a> DataSetView<'a> {
+    pub fn new(dataset: &'a DataSet, rows: Vec<IdxSize>) -> Self {
+        DataSetView { dataset, rows, seed: None }
+    }
+
+    /// The indices of the rows in the underlying dataset.
+    pub fn rows(&self) -> &[IdxSize] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Copy the rows into a new `DataSet`, keeping the dataset's `uuid`, `timestamp` and `info`.
+    pub fn to_dataset(&self) -> Result<DataSet, Box<dyn Error>> {
+        let mut dataset = self.dataset.with_data(take_rows(&self.dataset.data, self.rows.clone())?);
+        if self.seed.is_some() {
+            dataset.info.seed = self.seed;
+        }
+        Ok(dataset)
+    }
+
+    /// The rows as a feature matrix and a target vector, as taken by `linfa::Dataset::new`.
+    ///
+    /// Every column but `target_column` is a feature; all of them must be numeric.
+    pub fn to_ndarray(&self, target_column: &str) -> Result<(Array2<f64>, Array1<f64>), Box<dyn Error>> {
+        let data = take_rows(&self.dataset.data, self.rows.clone())?;
+        let records = data.drop(target_column)?.to_ndarray::<Float64Type>(IndexOrder::C)?;
+        let target = data.column(target_column)?.cast(&DataType::Float64)?;
+        let targets = target.f64()?.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect();
+        Ok((records, targets))
+    }
+}
+
+/// An iterator over the `(train, validation)` pairs of a cross-validation scheme.
+///
+/// Each round assigns every row to one of its folds; each fold of each round is the validation
+/// set once, with the rows of the other folds as the training set. Schemes where a row is in
+/// neither set, such as backtesting windows, list their `(train, validation)` rows explicitly.
+pub struct Folds<'a> {
+    dataset: &'a DataSet,
+    /// For each round, the fold of every row and the number of folds.
+    rounds: Vec<(Vec<usize>, usize)>,
+    round: usize,
+    fold: usize,
+    windows: std::vec::IntoIter<(Vec<IdxSize>, Vec<IdxSize>)>,
+}
+
+impl<'a> Folds<'a> {
+    fn new(dataset: &'a DataSet, rounds: Vec<(Vec<usize>, usize)>) -> Self {
+        Folds { dataset, rounds, round: 0, fold: 0, windows: Vec::new().into_iter() }
+    }
+
+    /// Folds with explicitly listed `(train, validation)` rows.
+    pub(crate) fn from_windows(dataset: &'a DataSet, windows: Vec<(Vec<IdxSize>, Vec<IdxSize>)>) -> Self {
+        Folds { dataset, rounds: Vec::new(), round: 0, fold: 0, windows: windows.into_iter() }
+    }
+}
+
+impl<'a> Iterator for Folds<'a> {
+    type Item = (DataSetView<'a>, DataSetView<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some((fold_of, k)) = self.rounds.get(self.round) else {
+            let (train, validation) = self.windows.next()?;
+            return Some((DataSetView::new(self.dataset, train), DataSetView::new(self.dataset, validation)));
+        };
+        let mut train = Vec::new();
+        let mut validation = Vec::new();
+        for (row, fold) in fold_of.iter().enumerate() {
+            if *fold == self.fold {
+                validation.push(row as IdxSize);
+            } else {
+                train.push(row as IdxSize);
+            }
+        }
+
+        self.fold += 1;
+        if self.fold == *k {
+            self.fold = 0;
+            self.round += 1;
+        }
+        Some((DataSetView::new(self.dataset, train), DataSetView::new(self.dataset, validation)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let folds = self.rounds.iter().skip(self.round).map(|(_, k)| k).sum::<usize>() - self.fold;
+        let remaining = folds + self.windows.len();
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Folds<'_> {}
+
+/// Check that `k` folds can be made out of `n` rows or groups, described as `items` in errors.
+fn check_k(k: usize, n: usize, items: &str) -> Result<(), Box<dyn Error>> {
+    if k < 2 {
+        return Err(format!("Cross-validation needs at least 2 folds, got {}", k).into());
+    }
+    if k > n {
+        return Err(format!("Cannot make {} folds out of {} {}", k, n, items).into());
+    }
+    Ok(())
+}
+
+/// Assign `n` rows to `k` contiguous folds, after shuffling them with `seed` if one is given.
+fn k_fold_assignment(n: usize, k: usize, seed: Option<u64>) -> Vec<usize> {
+    let order: Vec<IdxSize> = match seed {
+        Some(seed) => permutation(n, seed),
+        None => (0..n as IdxSize).collect(),
+    };
+    let mut fold_of = vec![0; n];
+    for (position, row) in order.into_iter().enumerate() {
+        fold_of[row as usize] = position * k / n;
+    }
+    fold_of
+}
+
+impl DataSet {
+    /// K-fold cross-validation: `k` folds of contiguous rows, shuffled first with `seed` if given.
+    pub fn k_fold(&self, k: usize, seed: Option<u64>) -> Result<Folds<'_>, Box<dyn Error>> {
+        let n = self.data.height();
+        check_k(k, n, "rows")?;
+        Ok(Folds::new(self, vec![(k_fold_assignment(n, k, seed), k)]))
+    }
+
+    /// K-fold cross-validation run `repeats` times, each time with a different shuffle drawn from `seed`.
+    pub fn repeated_k_fold(&self, k: usize, repeats: usize, seed: u64) -> Result<Folds<'_>, Box<dyn Error>> {
+        let n = self.data.height();
+        check_k(k, n, "rows")?;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let rounds = (0..repeats).map(|_| (k_fold_assignment(n, k, Some(rng.gen())), k)).collect();
+        Ok(Folds::new(self, rounds))
+    }
+
+    /// K-fold cross-validation keeping the class proportions of `target_column` in every fold.
+    ///
+    /// The rows of each class are dealt over the folds in turn, after shuffling them with `seed`
+    /// if given. Fails if a class has fewer than `k` rows, since some fold would then lack it.
+    pub fn stratified_k_fold(
+        &self,
+        target_column: &str,
+        k: usize,
+        seed: Option<u64>,
+    ) -> Result<Folds<'_>, Box<dyn Error>> {
+        let labels = self.data.column(target_column)?;
+        check_k(k, labels.len(), "rows")?;
+
+        let mut rng = seed.map(StdRng::seed_from_u64);
+        let mut fold_of = vec![0; labels.len()];
+        let mut dealt = 0;
+        for (label, mut rows) in class_indices(labels)? {
+            if rows.len() < k {
+                return Err(format!(
+                    "Class {} of column {} has {} rows, too few for {} folds",
+                    label.as_deref().unwrap_or("null"),
+                    target_column,
+                    rows.len(),
+                    k
+                )
+                .into());
+            }
+            if let Some(rng) = rng.as_mut() {
+                rows.shuffle(rng);
+            }
+            // Continue where the previous class stopped so fold sizes stay balanced.
+            for row in rows {
+                fold_of[row as usize] = dealt % k;
+                dealt += 1;
+            }
+        }
+        Ok(Folds::new(self, vec![(fold_of, k)]))
+    }
+
+    /// K-fold cross-validation with every value of `group_column` in exactly one fold.
+    ///
+    /// Groups are placed largest first into the fold with the fewest rows, so fold sizes are
+    /// balanced by rows rather than by groups. Fails if there are fewer than `k` groups.
+    pub fn group_k_fold(&self, group_column: &str, k: usize) -> Result<Folds<'_>, Box<dyn Error>> {
+        let mut groups = class_indices(self.data.column(group_column)?)?;
+        check_k(k, groups.len(), &format!("groups of column {}", group_column))?;
+
+        // A stable sort keeps groups of equal size in order of first appearance.
+        groups.sort_by_key(|(_, rows)| std::cmp::Reverse(rows.len()));
+        let mut fold_sizes = vec![0; k];
+        let mut fold_of = vec![0; self.data.height()];
+        for (_, rows) in groups {
+            let fold = (0..k).min_by_key(|fold| fold_sizes[*fold]).unwrap_or(0);
+            fold_sizes[fold] += rows.len();
+            for row in rows {
+                fold_of[row as usize] = fold;
+            }
+        }
+        Ok(Folds::new(self, vec![(fold_of, k)]))
+    }
+
+    /// Leave-one-group-out cross-validation: each value of `group_column` is the validation set once.
+    pub fn leave_one_group_out(&self, group_column: &str) -> Result<Folds<'_>, Box<dyn Error>> {
+        let groups = class_indices(self.data.column(group_column)?)?;
+        if groups.len() < 2 {
+            return Err(format!("Column {} has {} groups; at least 2 are needed", group_column, groups.len()).into());
+        }
+
+        let mut fold_of = vec![0; self.data.height()];
+        for (fold, (_, rows)) in groups.iter().enumerate() {
+            for row in rows {
+                fold_of[*row as usize] = fold;
+            }
+        }
+        Ok(Folds::new(self, vec![(fold_of, groups.len())]))
+    }
+}
@@ -0,0 +1,307 @@
+//! # CSV Dialects
+//!
+//! This module describes how a CSV file is written: its delimiter, quoting and escaping, header,
+//! comment lines, null tokens, text encoding, leading rows to skip and column dtypes. `CsvOptions`
+//! is accepted by `DataSet::load_data_with`, `DataSet::load_many` (through `LoadManyOptions`),
+//! `DataSet::scan_with` and `load_dataset`.
+//!
+//! Polars only reads UTF-8 and only understands doubled quotes, so Latin-1 input is transcoded and
+//! escaped quotes (`\"`) are rewritten as doubled quotes before parsing. Such files are read into
+//! memory first, and cannot be scanned lazily.
+
+use polars::prelude::*;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use crate::sniff;
+
+/// Number of bytes inspected when sniffing the delimiter past skipped and comment lines.
+const SNIFF_LEN: usize = 8192;
+
+/// Text encoding of a CSV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// UTF-8, replacing invalid bytes with `�`.
+    LossyUtf8,
+    /// ISO-8859-1, as written by many Windows tools and appliances.
+    Latin1,
+}
+
+impl TextEncoding {
+    /// Parse an encoding name such as `utf8`, `utf8-lossy` or `latin1`.
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "utf8" | "utf-8" => Ok(TextEncoding::Utf8),
+            "utf8-lossy" | "utf-8-lossy" => Ok(TextEncoding::LossyUtf8),
+            "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" => Ok(TextEncoding::Latin1),
+            other => Err(format!("Unsupported encoding: {}", other).into()),
+        }
+    }
+}
+
+/// How a CSV file is laid out.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Field delimiter; `None` uses the delimiter sniffed from the file.
+    pub separator: Option<u8>,
+    /// Quote character; `None` disables quoting.
+    pub quote_char: Option<u8>,
+    /// Character escaping a quote inside a quoted field, such as `\`.
+    pub escape_char: Option<u8>,
+    pub has_header: bool,
+    /// Lines starting with this prefix are skipped.
+    pub comment_prefix: Option<String>,
+    /// Field values read as null, such as `-` or `N/A`.
+    pub null_values: Vec<String>,
+    pub encoding: TextEncoding,
+    /// Number of lines skipped before the header (or the first row).
+    pub skip_rows: usize,
+    /// Dtypes overriding the inferred ones, by column name. Headerless files name their columns
+    /// `column_1`, `column_2`, ...
+    pub dtypes: Vec<(String, DataType)>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            separator: None,
+            quote_char: Some(b'"'),
+            escape_char: None,
+            has_header: true,
+            comment_prefix: None,
+            null_values: Vec::new(),
+            encoding: TextEncoding::Utf8,
+            skip_rows: 0,
+            dtypes: Vec::new(),
+        }
+    }
+}
+
+impl CsvOptions {
+    /// The delimiter to parse with: the configured one, or else `sniffed`.
+    ///
+    /// When rows are skipped or comments allowed, the delimiter sniffed from the start of the
+    /// file may be wrong, so it is sniffed again from the data lines of `head`.
+    fn resolve_separator(&self, head: &[u8], sniffed: u8) -> u8 {
+        if let Some(separator) = self.separator {
+            return separator;
+        }
+        if self.skip_rows == 0 && self.comment_prefix.is_none() {
+            return sniffed;
+        }
+        let text = String::from_utf8_lossy(head);
+        let comment = self.comment_prefix.as_deref();
+        let lines: Vec<&str> = text
+            .lines()
+            .skip(self.skip_rows)
+            .filter(|line| !comment.is_some_and(|prefix| line.starts_with(prefix)))
+            .collect();
+        sniff::sniff_delimiter(&lines.join("\n"))
+    }
+
+    /// The dtypes to parse columns as, as a Polars schema.
+    fn schema_overwrite(&self) -> Option<SchemaRef> {
+        (!self.dtypes.is_empty()).then(|| {
+            Arc::new(Schema::from_iter(self.dtypes.iter().map(|(name, dtype)| Field::new(name, dtype.clone()))))
+        })
+    }
+
+    fn polars_null_values(&self) -> Option<NullValues> {
+        (!self.null_values.is_empty()).then(|| NullValues::AllColumns(self.null_values.clone()))
+    }
+
+    fn polars_encoding(&self) -> CsvEncoding {
+        match self.encoding {
+            TextEncoding::LossyUtf8 => CsvEncoding::LossyUtf8,
+            // Latin-1 is transcoded before parsing.
+            TextEncoding::Utf8 | TextEncoding::Latin1 => CsvEncoding::Utf8,
+        }
+    }
+
+    /// The dialect as canonical JSON, to fingerprint datasets read with it.
+    pub(crate) fn cache_key(&self) -> String {
+        let encoding = match self.encoding {
+            TextEncoding::Utf8 => "utf8",
+            TextEncoding::LossyUtf8 => "utf8-lossy",
+            TextEncoding::Latin1 => "latin1",
+        };
+        let dtypes: Vec<(&str, String)> =
+            self.dtypes.iter().map(|(name, dtype)| (name.as_str(), dtype.to_string())).collect();
+        serde_json::json!({
+            "separator": self.separator,
+            "quote_char": self.quote_char,
+            "escape_char": self.escape_char,
+            "has_header": self.has_header,
+            "comment_prefix": self.comment_prefix,
+            "null_values": self.null_values,
+            "encoding": encoding,
+            "skip_rows": self.skip_rows,
+            "dtypes": dtypes,
+        })
+        .to_string()
+    }
+
+    /// The Polars read options for this dialect, parsing fields split by `separator`.
+    pub fn read_options(&self, separator: u8) -> CsvReadOptions {
+        let null_values = self.polars_null_values();
+        CsvReadOptions::default()
+            .with_has_header(self.has_header)
+            .with_skip_rows(self.skip_rows)
+            .with_infer_schema_length(None)
+            .with_schema_overwrite(self.schema_overwrite())
+            .map_parse_options(|opts| {
+                opts.with_separator(separator)
+                    .with_quote_char(self.quote_char)
+                    .with_comment_prefix(self.comment_prefix.as_deref())
+                    .with_null_values(null_values.clone())
+                    .with_encoding(self.polars_encoding())
+            })
+    }
+
+    /// Returns `true` if the input must be rewritten before Polars can parse it.
+    fn needs_decoding(&self) -> bool {
+        self.encoding == TextEncoding::Latin1 || (self.escape_char.is_some() && self.escape_char != self.quote_char)
+    }
+
+    /// Transcode the input to UTF-8 and rewrite escaped quotes as doubled quotes.
+    fn decode(&self, bytes: Vec<u8>) -> Vec<u8> {
+        let bytes = match self.encoding {
+            // Every Latin-1 byte is the Unicode code point of the same value.
+            TextEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect::<String>().into_bytes(),
+            TextEncoding::Utf8 | TextEncoding::LossyUtf8 => bytes,
+        };
+        match (self.escape_char, self.quote_char) {
+            (Some(escape), Some(quote)) if escape != quote => unescape(&bytes, escape, quote),
+            _ => bytes,
+        }
+    }
+
+    /// Read CSV data from `reader`; `sniffed_separator` is used if no separator is set.
+    pub fn read<R: Read>(&self, mut reader: R, sniffed_separator: u8) -> Result<DataFrame, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let bytes = if self.needs_decoding() { self.decode(bytes) } else { bytes };
+        let separator = self.resolve_separator(&bytes[..bytes.len().min(SNIFF_LEN)], sniffed_separator);
+        Ok(self.read_options(separator).into_reader_with_file_handle(Cursor::new(bytes)).finish()?)
+    }
+
+    /// Read a CSV file, letting Polars map it directly when it needs no rewriting.
+    pub fn read_file<P: AsRef<Path>>(&self, file_path: P, sniffed_separator: u8) -> Result<DataFrame, Box<dyn Error>> {
+        let path = file_path.as_ref();
+        if self.needs_decoding() {
+            return self.read(File::open(path)?, sniffed_separator);
+        }
+
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+        let separator = self.resolve_separator(&head, sniffed_separator);
+        Ok(self
+            .read_options(separator)
+            .try_into_reader_with_file_path(Some(path.to_path_buf()))?
+            .finish()?)
+    }
+
+    /// Read a CSV file whose delimiter, unless set, is sniffed from its first lines.
+    pub fn read_file_sniffed<P: AsRef<Path>>(&self, file_path: P) -> Result<DataFrame, Box<dyn Error>> {
+        let path = file_path.as_ref();
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+        self.read_file(path, sniff::sniff_delimiter(&String::from_utf8_lossy(&head)))
+    }
+
+    /// Scan a CSV file lazily with this dialect; `sniffed_separator` is used if no separator is set.
+    pub fn scan_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        sniffed_separator: u8,
+    ) -> Result<LazyFrame, Box<dyn Error>> {
+        let path = file_path.as_ref();
+        if self.needs_decoding() {
+            let message = format!("Cannot scan {} lazily as it must be decoded; use load_data_with", path.display());
+            return Err(message.into());
+        }
+
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+        Ok(LazyCsvReader::new(path)
+            .with_has_header(self.has_header)
+            .with_skip_rows(self.skip_rows)
+            .with_dtype_overwrite(self.schema_overwrite())
+            .with_separator(self.resolve_separator(&head, sniffed_separator))
+            .with_quote_char(self.quote_char)
+            .with_comment_prefix(self.comment_prefix.as_deref())
+            .with_null_values(self.polars_null_values())
+            .with_encoding(self.polars_encoding())
+            .finish()?)
+    }
+}
+
+/// Rewrite `<escape><quote>` as a doubled quote and `<escape><escape>` as a single escape.
+///
+/// Both characters are ASCII, so this is safe on UTF-8 input.
+fn unescape(bytes: &[u8], escape: u8, quote: u8) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter().copied().peekable();
+    while let Some(b) = iter.next() {
+        if b == escape {
+            match iter.peek().copied() {
+                Some(next) if next == quote => {
+                    out.extend([quote, quote]);
+                    iter.next();
+                    continue;
+                }
+                Some(next) if next == escape => {
+                    out.push(escape);
+                    iter.next();
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// Parse a single-character option such as a delimiter; `\t` and `tab` mean a tab.
+pub fn parse_char(value: &str) -> Result<u8, Box<dyn Error>> {
+    match value {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if value.len() == 1 => Ok(value.as_bytes()[0]),
+        _ => Err(format!("Expected a single ASCII character, got {:?}", value).into()),
+    }
+}
+
+/// Parse a dtype name such as `i64`, `f32`, `str`, `bool`, `date` or `datetime`.
+pub fn parse_dtype(name: &str) -> Result<DataType, Box<dyn Error>> {
+    let dtype = match name.to_lowercase().as_str() {
+        "str" | "string" | "utf8" => DataType::String,
+        "bool" | "boolean" => DataType::Boolean,
+        "i8" | "int8" => DataType::Int8,
+        "i16" | "int16" => DataType::Int16,
+        "i32" | "int32" => DataType::Int32,
+        "i64" | "int64" | "int" => DataType::Int64,
+        "u8" | "uint8" => DataType::UInt8,
+        "u16" | "uint16" => DataType::UInt16,
+        "u32" | "uint32" => DataType::UInt32,
+        "u64" | "uint64" => DataType::UInt64,
+        "f32" | "float32" => DataType::Float32,
+        "f64" | "float64" | "float" => DataType::Float64,
+        "date" => DataType::Date,
+        "datetime" => DataType::Datetime(TimeUnit::Microseconds, None),
+        other => return Err(format!("Unknown dtype: {}", other).into()),
+    };
+    Ok(dtype)
+}
+
+/// Parse a `column=dtype` override.
+pub fn parse_dtype_override(value: &str) -> Result<(String, DataType), Box<dyn Error>> {
+    let (column, dtype) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected column=dtype, got {:?}", value))?;
+    Ok((column.to_string(), parse_dtype(dtype)?))
+}
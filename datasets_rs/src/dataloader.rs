@@ -0,0 +1,79 @@
+//! # DataLoader
+//!
+//! This module provides `DataLoader`, which iterates over a `DataSet` in batches of rows. When
+//! shuffling, each epoch visits the rows in an order derived from the loader's seed and the epoch
+//! number, so any epoch of a training run can be replayed exactly.
+
+use polars::prelude::*;
+
+use crate::load_dataset::DataSet;
+use crate::split::permutation;
+
+/// Iterates over a `DataSet` in batches, optionally shuffled with a seed.
+pub struct DataLoader {
+    dataset: DataSet,
+    batch_size: usize,
+    seed: Option<u64>,
+    epoch: u64,
+}
+
+impl DataLoader {
+    /// Create a loader yielding `batch_size` rows at a time; `seed` enables shuffling.
+    pub fn new(dataset: DataSet, batch_size: usize, seed: Option<u64>) -> Self {
+        DataLoader { dataset, batch_size: batch_size.max(1), seed, epoch: 0 }
+    }
+
+    /// Number of batches per epoch, counting a final partial batch.
+    pub fn num_batches(&self) -> usize {
+        self.dataset.data.height().div_ceil(self.batch_size)
+    }
+
+    /// The next epoch to be returned by `next_epoch`.
+    pub fn current_epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The row order of `epoch`.
+    fn epoch_rows(&self, epoch: u64) -> Vec<IdxSize> {
+        let n = self.dataset.data.height();
+        match self.seed {
+            // Spread the epochs apart so that seed `s`, epoch 1 is unrelated to seed `s + 1`, epoch 0.
+            Some(seed) => permutation(n, seed ^ epoch.wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+            None => (0..n as IdxSize).collect(),
+        }
+    }
+
+    /// The batches of `epoch`, without advancing the loader.
+    pub fn epoch(&self, epoch: u64) -> Batches {
+        Batches { data: self.dataset.data.clone(), rows: self.epoch_rows(epoch), batch_size: self.batch_size, position: 0 }
+    }
+
+    /// The batches of the next epoch.
+    pub fn next_epoch(&mut self) -> Batches {
+        let batches = self.epoch(self.epoch);
+        self.epoch += 1;
+        batches
+    }
+}
+
+/// The batches of one epoch.
+pub struct Batches {
+    data: DataFrame,
+    rows: Vec<IdxSize>,
+    batch_size: usize,
+    position: usize,
+}
+
+impl Iterator for Batches {
+    type Item = PolarsResult<DataFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.rows.len() {
+            return None;
+        }
+        let end = (self.position + self.batch_size).min(self.rows.len());
+        let rows = self.rows[self.position..end].to_vec();
+        self.position = end;
+        Some(self.data.take(&IdxCa::from_vec("", rows)))
+    }
+}
@@ -0,0 +1,265 @@
+//! # Dataset Dicts
+//!
+//! This module provides `DatasetDict`, a set of named splits such as `train`, `validation` and
+//! `test`, each held in a `DataSet`. Splits keep the order they were added in. Transforms are
+//! applied to every split, and all splits can be saved to and loaded from one directory, either as
+//! one file per split next to a `splits.json` manifest holding each split's `info` and fingerprint,
+//! or in the Hugging Face `save_to_disk` layout.
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::ops::Index;
+use std::path::{Path, PathBuf};
+
+use crate::compression::{self, Compression};
+use crate::hf_disk::{self, DiskDataset};
+use crate::load_dataset::{DataSet, DatasetInfo};
+use crate::sniff;
+
+pub const MANIFEST_FILE: &str = "splits.json";
+
+/// A split file listed in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitEntry {
+    pub name: String,
+    pub filename: String,
+    pub num_rows: usize,
+    #[serde(default)]
+    pub info: DatasetInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+/// The contents of `splits.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitManifest {
+    pub splits: Vec<SplitEntry>,
+}
+
+/// Named splits of a dataset.
+#[derive(Clone, Default)]
+pub struct DatasetDict {
+    splits: Vec<(String, DataSet)>,
+}
+
+/// The split a data file holds when there is no manifest: its file name without its format and
+/// compression extensions.
+fn split_name(file: &Path) -> Option<&str> {
+    let mut stem = Path::new(file.file_stem()?);
+    if compression::path_format(file).1 != Compression::None {
+        stem = Path::new(stem.file_stem()?);
+    }
+    stem.to_str()
+}
+
+/// Sort key putting `train`, `validation` and `test` first.
+fn split_rank(name: &str) -> usize {
+    match name {
+        "train" => 0,
+        "validation" => 1,
+        "test" => 2,
+        _ => 3,
+    }
+}
+
+impl DatasetDict {
+    /// Create an empty `DatasetDict`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a split, returning the split it replaced, if any.
+    pub fn insert<S: Into<String>>(&mut self, name: S, dataset: DataSet) -> Option<DataSet> {
+        let name = name.into();
+        match self.splits.iter_mut().find(|(split, _)| *split == name) {
+            Some((_, existing)) => Some(std::mem::replace(existing, dataset)),
+            None => {
+                self.splits.push((name, dataset));
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DataSet> {
+        self.splits.iter().find(|(split, _)| split == name).map(|(_, dataset)| dataset)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut DataSet> {
+        self.splits.iter_mut().find(|(split, _)| split == name).map(|(_, dataset)| dataset)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<DataSet> {
+        let position = self.splits.iter().position(|(split, _)| split == name)?;
+        Some(self.splits.remove(position).1)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The split names, in order.
+    pub fn split_names(&self) -> Vec<&str> {
+        self.splits.iter().map(|(split, _)| split.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.splits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.splits.is_empty()
+    }
+
+    /// Iterate over the splits and their names, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &DataSet)> {
+        self.splits.iter().map(|(split, dataset)| (split.as_str(), dataset))
+    }
+
+    /// Total number of rows over all splits.
+    pub fn num_rows(&self) -> usize {
+        self.splits.iter().map(|(_, dataset)| dataset.data.height()).sum()
+    }
+
+    /// Order the splits as `train`, `validation`, `test`, then the others by name.
+    pub fn sort_splits(&mut self) {
+        self.splits.sort_by(|(a, _), (b, _)| (split_rank(a), a).cmp(&(split_rank(b), b)));
+    }
+
+    /// Apply `f` to the data of every split, keeping each split's `info`.
+    pub fn map<F>(&self, f: F) -> Result<Self, Box<dyn Error>>
+    where
+        F: Fn(&DataFrame) -> Result<DataFrame, Box<dyn Error>>,
+    {
+        let mut splits = Vec::with_capacity(self.splits.len());
+        for (split, dataset) in &self.splits {
+            splits.push((split.clone(), dataset.with_data(f(&dataset.data)?)));
+        }
+        Ok(DatasetDict { splits })
+    }
+
+    /// Keep the rows of every split matching `predicate`.
+    pub fn filter(&self, predicate: Expr) -> Result<Self, Box<dyn Error>> {
+        self.map(|df| Ok(df.clone().lazy().filter(predicate.clone()).collect()?))
+    }
+
+    /// Save every split as `<dir>/<split>.<file_extension>`, listed in `<dir>/splits.json`.
+    pub fn save_data<P: AsRef<Path>>(&self, dir: P, file_extension: &str) -> Result<(), Box<dyn Error>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut splits = Vec::with_capacity(self.splits.len());
+        for (split, dataset) in &self.splits {
+            let filename = format!("{}.{}", split, file_extension);
+            if filename == MANIFEST_FILE {
+                return Err(format!("Split {} would overwrite the manifest {}", split, MANIFEST_FILE).into());
+            }
+            dataset.save_data(dir.join(&filename), file_extension)?;
+            splits.push(SplitEntry {
+                name: split.clone(),
+                filename,
+                num_rows: dataset.data.height(),
+                info: dataset.info.clone(),
+                fingerprint: dataset.fingerprint.clone(),
+            });
+        }
+        let manifest = SplitManifest { splits };
+        serde_json::to_writer_pretty(BufWriter::new(File::create(dir.join(MANIFEST_FILE))?), &manifest)?;
+        Ok(())
+    }
+
+    /// Load a directory written by `save_data`, restoring each split's `info` and fingerprint.
+    ///
+    /// A directory without a manifest is loaded file by file, each file becoming the split named
+    /// by its file name without its extensions, such as `train.v2` for `train.v2.csv.gz`.
+    pub fn load_data<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.as_ref();
+        let manifest_path = dir.join(MANIFEST_FILE);
+        if manifest_path.exists() {
+            let manifest: SplitManifest = serde_json::from_reader(BufReader::new(File::open(manifest_path)?))?;
+            let mut dict = DatasetDict::new();
+            for entry in manifest.splits {
+                let mut dataset = DataSet::new(DataSet::load_data(dir.join(&entry.filename))?);
+                dataset.info = entry.info;
+                dataset.fingerprint = entry.fingerprint;
+                dict.insert(entry.name, dataset);
+            }
+            return Ok(dict);
+        }
+
+        let mut files = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        files.retain(|path| path.is_file() && sniff::from_extension(path).is_some());
+        if files.is_empty() {
+            return Err(format!("No data files found in {}", dir.display()).into());
+        }
+
+        let mut dict = DatasetDict::new();
+        for file in files {
+            let split = split_name(&file).ok_or_else(|| format!("Cannot name a split after {}", file.display()))?;
+            if dict.contains(split) {
+                return Err(format!("Several files in {} hold split {}", dir.display(), split).into());
+            }
+            dict.insert(split, DataSet::new(DataSet::load_data(&file)?));
+        }
+        dict.sort_splits();
+        Ok(dict)
+    }
+
+    /// Save all splits in the Hugging Face `DatasetDict.save_to_disk` layout.
+    pub fn save_to_disk<P: AsRef<Path>>(&self, dir: P, num_shards: usize) -> Result<(), Box<dyn Error>> {
+        hf_disk::save_dict_to_disk(self, dir, num_shards)
+    }
+
+    /// Load a directory written by Hugging Face `DatasetDict.save_to_disk`.
+    pub fn load_from_disk<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn Error>> {
+        match hf_disk::load_from_disk(dir)? {
+            DiskDataset::Dict(dict) => Ok(dict),
+            DiskDataset::Single(_) => Err("Directory holds a single Dataset; use DataSet::load_from_disk".into()),
+        }
+    }
+}
+
+impl Index<&str> for DatasetDict {
+    type Output = DataSet;
+
+    fn index(&self, name: &str) -> &DataSet {
+        self.get(name).unwrap_or_else(|| panic!("No split named {}", name))
+    }
+}
+
+impl FromIterator<(String, DataSet)> for DatasetDict {
+    fn from_iter<I: IntoIterator<Item = (String, DataSet)>>(iter: I) -> Self {
+        let mut dict = DatasetDict::new();
+        for (split, dataset) in iter {
+            dict.insert(split, dataset);
+        }
+        dict
+    }
+}
+
+impl IntoIterator for DatasetDict {
+    type Item = (String, DataSet);
+    type IntoIter = std::vec::IntoIter<(String, DataSet)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.splits.into_iter()
+    }
+}
+
+/// A summary in the style of Hugging Face `datasets`, listing each split's columns and rows.
+impl fmt::Display for DatasetDict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "DatasetDict({{")?;
+        for (split, dataset) in &self.splits {
+            writeln!(f, "    {}: DataSet({{", split)?;
+            writeln!(f, "        features: {:?},", dataset.data.get_column_names())?;
+            writeln!(f, "        num_rows: {}", dataset.data.height())?;
+            writeln!(f, "    }})")?;
+        }
+        write!(f, "}})")
+    }
+}
@@ -0,0 +1,318 @@
+//! # Remote Downloads
+//!
+//! This module fetches `http://` and `https://` data files into a content-addressed cache, so
+//! `DataSet::load_data`, `DataSet::load_many` and `load_dataset` can take URLs. The cache lives in
+//! `<cache root>/downloads` (see `hub::cache_root`):
+//!
+//! - `blobs/<sha256>` holds each downloaded file, named after the SHA-256 of its content;
+//! - `refs/<sha256 of the url>.json` maps a URL to its blob and the `ETag` it was served with;
+//! - `incomplete/<sha256 of the url>.part` holds an interrupted download, which the next attempt
+//!   resumes with a `Range` request. The download writing it holds `incomplete/<sha256 of the
+//!   url>.lock`; a concurrent download of the same URL starts over in a file of its own instead.
+//!
+//! Cached URLs are revalidated with `If-None-Match`. When the server cannot be reached, or when
+//! `DATASETS_RS_OFFLINE` is set, the cached copy is used as is.
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::hub;
+
+/// Environment variable that, when set to anything but `0`, disables network access.
+pub const OFFLINE_ENV: &str = "DATASETS_RS_OFFLINE";
+
+/// Size of the buffer used to stream a response to disk.
+const CHUNK_SIZE: usize = 1 << 16;
+
+type SendError = Box<dyn Error + Send + Sync>;
+
+/// Options for `download` and `download_many`.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Directory of the download cache.
+    pub cache_dir: PathBuf,
+    /// Only use the cache and never touch the network.
+    pub offline: bool,
+    /// Expected SHA-256 of the content as hex; only used by `download`.
+    pub sha256: Option<String>,
+    /// Number of files `download_many` fetches at the same time.
+    pub concurrency: usize,
+    /// Attempts per file; each attempt resumes where the previous one stopped.
+    pub retries: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            cache_dir: hub::cache_root().join("downloads"),
+            offline: env::var_os(OFFLINE_ENV).is_some_and(|v| !v.is_empty() && v != "0"),
+            sha256: None,
+            concurrency: 8,
+            retries: 3,
+        }
+    }
+}
+
+impl DownloadOptions {
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.cache_dir.join("blobs").join(sha256)
+    }
+
+    fn ref_path(&self, url: &str) -> PathBuf {
+        self.cache_dir.join("refs").join(format!("{}.json", sha256_hex(url.as_bytes())))
+    }
+
+    fn part_path(&self, url: &str) -> PathBuf {
+        self.cache_dir.join("incomplete").join(format!("{}.part", sha256_hex(url.as_bytes())))
+    }
+
+    /// Claim the `.part` file of `url` with its lock file, or a fresh file of this download's own
+    /// if another download holds the lock.
+    fn claim_part(&self, url: &str) -> io::Result<PartFile> {
+        fs::create_dir_all(self.cache_dir.join("incomplete"))?;
+        let path = self.part_path(url);
+        let lock = path.with_extension("lock");
+        let take = || OpenOptions::new().write(true).create_new(true).open(&lock).is_ok();
+        // A lock left behind by a killed download goes stale once neither it nor the `.part` changes.
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let stale = || {
+            let last_change = modified(&lock).max(modified(&path));
+            last_change.is_some_and(|time| time.elapsed().is_ok_and(|age| age > STALE_LOCK))
+        };
+        if take() || (stale() && fs::remove_file(&lock).is_ok() && take()) {
+            return Ok(PartFile { path, lock: Some(lock) });
+        }
+
+        static CLAIMS: AtomicUsize = AtomicUsize::new(0);
+        let claim = CLAIMS.fetch_add(1, Ordering::Relaxed);
+        let name = format!("{}.{}-{}.part", sha256_hex(url.as_bytes()), process::id(), claim);
+        Ok(PartFile { path: self.cache_dir.join("incomplete").join(name), lock: None })
+    }
+}
+
+/// Age after which the lock of an unchanged `.part` file is taken over.
+const STALE_LOCK: Duration = Duration::from_secs(300);
+
+/// The file a download is written to before it is moved into `blobs`.
+struct PartFile {
+    path: PathBuf,
+    /// The lock file held while writing the shared, resumable `.part` of a URL; `None` for a file
+    /// of this download's own, which is removed if the download fails.
+    lock: Option<PathBuf>,
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.lock.as_ref().unwrap_or(&self.path));
+    }
+}
+
+/// The cache record of a downloaded URL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Returns `true` if `source` is an `http://` or `https://` URL.
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// The hex SHA-256 of a file's content.
+pub(crate) fn file_sha256(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The SHA-256 carried by an `ETag`, as served for files stored with Git LFS.
+fn etag_sha256(etag: &str) -> Option<String> {
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    (etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit())).then(|| etag.to_lowercase())
+}
+
+/// Look up the cached copy of `url`, if its blob is still present.
+pub fn cached(url: &str, options: &DownloadOptions) -> Option<(CacheEntry, PathBuf)> {
+    let file = File::open(options.ref_path(url)).ok()?;
+    let entry: CacheEntry = serde_json::from_reader(BufReader::new(file)).ok()?;
+    let blob = options.blob_path(&entry.sha256);
+    blob.is_file().then_some((entry, blob))
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(30))
+        .timeout_read(Duration::from_secs(60))
+        .try_proxy_from_env(true)
+        .build()
+}
+
+/// Download `url` into the cache and return the path of its blob.
+///
+/// A cached copy whose hash matches `options.sha256` is returned without a request. Otherwise
+/// the cached copy is revalidated, and used as a fallback when every attempt fails.
+pub fn download(url: &str, options: &DownloadOptions) -> Result<PathBuf, Box<dyn Error>> {
+    download_blob(url, options).map_err(|e| e as Box<dyn Error>)
+}
+
+/// `download`, with errors that can be sent back from the worker threads of `download_many`.
+fn download_blob(url: &str, options: &DownloadOptions) -> Result<PathBuf, SendError> {
+    let cached = cached(url, options).filter(|(entry, _)| {
+        options.sha256.as_ref().map_or(true, |expected| expected.eq_ignore_ascii_case(&entry.sha256))
+    });
+    if options.offline || (options.sha256.is_some() && cached.is_some()) {
+        return cached
+            .map(|(_, blob)| blob)
+            .ok_or_else(|| format!("{} is not in the download cache {}", url, options.cache_dir.display()).into());
+    }
+
+    let mut last_error = None;
+    for attempt in 1..=options.retries.max(1) {
+        match fetch(url, cached.as_ref().map(|(entry, _)| entry), options) {
+            Ok(blob) => return Ok(blob),
+            Err(e) => {
+                log::warn!("Download of {} failed (attempt {}): {}", url, attempt, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match cached {
+        Some((_, blob)) => {
+            log::warn!("Using the cached copy of {}", url);
+            Ok(blob)
+        }
+        None => Err(last_error.unwrap_or_else(|| format!("Download of {} failed", url).into())),
+    }
+}
+
+/// Make one attempt at downloading `url`, resuming a partial download if there is one and no
+/// other download of `url` is under way.
+fn fetch(url: &str, cached: Option<&CacheEntry>, options: &DownloadOptions) -> Result<PathBuf, SendError> {
+    let claim = options.claim_part(url)?;
+    let resumable = claim.lock.is_some();
+    let part = &claim.path;
+    let part_etag = part.with_extension("etag");
+    let resume_from = if resumable { fs::metadata(part).map(|m| m.len()).unwrap_or(0) } else { 0 };
+
+    // Ranges refer to the encoded bytes, so ask for the file as stored to keep resuming sound.
+    let mut request = agent().get(url).set("Accept-Encoding", "identity");
+    if let Some(etag) = cached.and_then(|entry| entry.etag.as_deref()) {
+        request = request.set("If-None-Match", etag);
+    }
+    if resume_from > 0 {
+        request = request.set("Range", &format!("bytes={}-", resume_from));
+        // Only resume if the file has not changed since the partial download started.
+        if let Ok(etag) = fs::read_to_string(&part_etag) {
+            request = request.set("If-Range", etag.trim());
+        }
+    }
+
+    let response = match request.call() {
+        Err(ureq::Error::Status(416, _)) => {
+            fs::remove_file(part)?;
+            return Err(format!("Server rejected resuming {}; restarting", url).into());
+        }
+        response => response?,
+    };
+    if response.status() == 304 {
+        let entry = cached.ok_or_else(|| format!("Unexpected 304 Not Modified for {}", url))?;
+        log::info!("{} is unchanged since it was cached", url);
+        return Ok(options.blob_path(&entry.sha256));
+    }
+
+    let etag = response.header("ETag").map(str::to_string);
+    let content_length = response.header("Content-Length").and_then(|len| len.parse::<u64>().ok());
+    let mut hasher = Sha256::new();
+    let mut file = if response.status() == 206 {
+        log::info!("Resuming {} at byte {}", url, resume_from);
+        io::copy(&mut File::open(part)?, &mut hasher)?;
+        OpenOptions::new().append(true).open(part)?
+    } else {
+        File::create(part)?
+    };
+    if resumable {
+        match &etag {
+            Some(etag) => fs::write(&part_etag, etag)?,
+            None => {
+                let _ = fs::remove_file(&part_etag);
+            }
+        }
+    }
+
+    let mut reader = response.into_reader();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut received = 0u64;
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buffer[..n])?;
+        hasher.update(&buffer[..n]);
+        received += n as u64;
+    }
+    file.flush()?;
+    if let Some(len) = content_length {
+        if received != len {
+            return Err(format!("Download of {} stopped after {} of {} bytes", url, received, len).into());
+        }
+    }
+
+    let sha256 = hex::encode(hasher.finalize());
+    let expected = options.sha256.clone().or_else(|| etag.as_deref().and_then(etag_sha256));
+    if let Some(expected) = expected {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            fs::remove_file(part)?;
+            return Err(format!("Checksum mismatch for {}: expected {}, got {}", url, expected, sha256).into());
+        }
+    }
+
+    let blob = options.blob_path(&sha256);
+    fs::create_dir_all(options.cache_dir.join("blobs"))?;
+    fs::rename(part, &blob)?;
+    if resumable {
+        let _ = fs::remove_file(&part_etag);
+    }
+
+    let entry = CacheEntry { url: url.to_string(), etag, sha256, size: fs::metadata(&blob)?.len() };
+    fs::create_dir_all(options.cache_dir.join("refs"))?;
+    serde_json::to_writer_pretty(BufWriter::new(File::create(options.ref_path(url))?), &entry)?;
+    log::info!("Downloaded {} ({} bytes, sha256 {})", url, entry.size, entry.sha256);
+    Ok(blob)
+}
+
+/// Download several URLs, `options.concurrency` at a time, keeping their order; the first URL
+/// that fails gives its error as returned by `download`.
+pub fn download_many<S: AsRef<str> + Sync>(
+    urls: &[S],
+    options: &DownloadOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if urls.is_empty() {
+        return Ok(Vec::new());
+    }
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(options.concurrency.max(1)).build()?;
+    let options = DownloadOptions { sha256: None, ..options.clone() };
+    let blobs = pool.install(|| {
+        urls.par_iter()
+            .map(|url| download_blob(url.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()
+    });
+    blobs.map_err(|e| e as Box<dyn Error>)
+}
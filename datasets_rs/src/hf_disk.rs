@@ -0,0 +1,251 @@
+//! # Hugging Face `save_to_disk` Layout
+//!
+//! This module reads and writes the directory layout produced by `datasets.Dataset.save_to_disk`
+//! and `datasets.DatasetDict.save_to_disk`:
+//!
+//! - a dataset directory holds Arrow IPC stream shards named `data-00000-of-0000N.arrow`, a
+//!   `dataset_info.json` with the `features`, and a `state.json` listing the shards;
+//! - a dataset dict directory holds a `dataset_dict.json` naming the splits, with one dataset
+//!   directory per split.
+//!
+//! Directories written here can be opened from Python with `datasets.load_from_disk`.
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::dataset_dict::DatasetDict;
+use crate::load_dataset::{DataSet, DatasetInfo};
+use crate::shard::shard_range;
+
+const DATASET_INFO_FILE: &str = "dataset_info.json";
+const STATE_FILE: &str = "state.json";
+const DATASET_DICT_FILE: &str = "dataset_dict.json";
+
+/// A shard entry in `state.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DataFile {
+    pub filename: String,
+}
+
+/// The contents of `state.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatasetState {
+    #[serde(rename = "_data_files")]
+    pub data_files: Vec<DataFile>,
+    #[serde(rename = "_fingerprint")]
+    pub fingerprint: String,
+    #[serde(rename = "_format_columns", default)]
+    pub format_columns: Option<Vec<String>>,
+    #[serde(rename = "_format_kwargs", default)]
+    pub format_kwargs: serde_json::Map<String, Value>,
+    #[serde(rename = "_format_type", default)]
+    pub format_type: Option<String>,
+    #[serde(rename = "_output_all_columns", default)]
+    pub output_all_columns: bool,
+    #[serde(rename = "_split", default)]
+    pub split: Option<String>,
+}
+
+/// The contents of `dataset_dict.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatasetDictState {
+    pub splits: Vec<String>,
+}
+
+/// A directory loaded with `load_from_disk`: either a single dataset or one dataset per split.
+pub enum DiskDataset {
+    Single(Box<DataSet>),
+    Dict(DatasetDict),
+}
+
+/// Describe a Polars dtype as a Hugging Face feature.
+pub fn dtype_to_feature(dtype: &DataType) -> Value {
+    let value = |dtype: &str| json!({ "dtype": dtype, "_type": "Value" });
+    match dtype {
+        DataType::Boolean => value("bool"),
+        DataType::Int8 => value("int8"),
+        DataType::Int16 => value("int16"),
+        DataType::Int32 => value("int32"),
+        DataType::Int64 => value("int64"),
+        DataType::UInt8 => value("uint8"),
+        DataType::UInt16 => value("uint16"),
+        DataType::UInt32 => value("uint32"),
+        DataType::UInt64 => value("uint64"),
+        DataType::Float32 => value("float32"),
+        DataType::Float64 => value("float64"),
+        // Written with `CompatLevel::oldest`, so strings and binaries are the large variants.
+        DataType::String => value("large_string"),
+        DataType::Binary => value("large_binary"),
+        DataType::Date => value("date32"),
+        DataType::Datetime(unit, tz) => {
+            let unit = match unit {
+                TimeUnit::Nanoseconds => "ns",
+                TimeUnit::Microseconds => "us",
+                TimeUnit::Milliseconds => "ms",
+            };
+            match tz {
+                Some(tz) => value(&format!("timestamp[{}, tz={}]", unit, tz)),
+                None => value(&format!("timestamp[{}]", unit)),
+            }
+        }
+        DataType::Null => value("null"),
+        DataType::List(inner) => json!({ "feature": dtype_to_feature(inner), "_type": "Sequence" }),
+        DataType::Struct(fields) => {
+            let map: serde_json::Map<String, Value> = fields
+                .iter()
+                .map(|f| (f.name().to_string(), dtype_to_feature(f.data_type())))
+                .collect();
+            Value::Object(map)
+        }
+        other => value(&other.to_string()),
+    }
+}
+
+/// Describe a `DataFrame` schema as Hugging Face `features`.
+pub fn features_from_schema(schema: &Schema) -> Value {
+    let map: serde_json::Map<String, Value> = schema
+        .iter()
+        .map(|(name, dtype)| (name.to_string(), dtype_to_feature(dtype)))
+        .collect();
+    Value::Object(map)
+}
+
+/// Load a single dataset directory written by `Dataset.save_to_disk`.
+pub fn load_dataset_dir<P: AsRef<Path>>(dir: P) -> Result<DataSet, Box<dyn Error>> {
+    let dir = dir.as_ref();
+    let state: DatasetState = serde_json::from_reader(BufReader::new(File::open(dir.join(STATE_FILE))?))?;
+
+    let mut data: Option<DataFrame> = None;
+    for data_file in &state.data_files {
+        let file = BufReader::new(File::open(dir.join(&data_file.filename))?);
+        let shard = IpcStreamReader::new(file).finish()?;
+        match data.as_mut() {
+            Some(df) => {
+                df.vstack_mut(&shard)?;
+            }
+            None => data = Some(shard),
+        }
+    }
+    let mut data = data.ok_or_else(|| format!("No data files listed in {}", dir.join(STATE_FILE).display()))?;
+    data.align_chunks();
+
+    let info_path = dir.join(DATASET_INFO_FILE);
+    let info: DatasetInfo = if info_path.exists() {
+        serde_json::from_reader(BufReader::new(File::open(info_path)?))?
+    } else {
+        DatasetInfo::default()
+    };
+
+    let mut dataset = DataSet::new(data);
+    dataset.info = info;
+    dataset.fingerprint = Some(state.fingerprint);
+    Ok(dataset)
+}
+
+/// Load a directory written by `Dataset.save_to_disk` or `DatasetDict.save_to_disk`.
+pub fn load_from_disk<P: AsRef<Path>>(dir: P) -> Result<DiskDataset, Box<dyn Error>> {
+    let dir = dir.as_ref();
+    let dict_path = dir.join(DATASET_DICT_FILE);
+    if !dict_path.exists() {
+        return Ok(DiskDataset::Single(Box::new(load_dataset_dir(dir)?)));
+    }
+
+    let dict: DatasetDictState = serde_json::from_reader(BufReader::new(File::open(dict_path)?))?;
+    let mut splits = DatasetDict::new();
+    for split in dict.splits {
+        let dataset = load_dataset_dir(dir.join(&split))?;
+        splits.insert(split, dataset);
+    }
+    Ok(DiskDataset::Dict(splits))
+}
+
+/// Write a dataset directory that `datasets.load_from_disk` can open.
+///
+/// The rows are spread over `num_shards` contiguous Arrow IPC stream files, as by `DataSet::shard`. Features are taken from
+/// `dataset.info` when present, otherwise they are derived from the `DataFrame` schema.
+pub fn save_dataset_dir<P: AsRef<Path>>(
+    dataset: &DataSet,
+    dir: P,
+    num_shards: usize,
+) -> Result<(), Box<dyn Error>> {
+    write_dataset_dir(dataset, dir.as_ref(), num_shards, None)
+}
+
+fn write_dataset_dir(
+    dataset: &DataSet,
+    dir: &Path,
+    num_shards: usize,
+    split: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+
+    let num_shards = num_shards.max(1);
+    let height = dataset.data.height();
+    let mut data_files = Vec::with_capacity(num_shards);
+    for shard in 0..num_shards {
+        let rows = shard_range(height, num_shards, shard);
+        let mut df = dataset.data.slice(rows.start as i64, rows.len());
+
+        let filename = format!("data-{:05}-of-{:05}.arrow", shard, num_shards);
+        let file = BufWriter::new(File::create(dir.join(&filename))?);
+        IpcStreamWriter::new(file)
+            .with_compat_level(CompatLevel::oldest())
+            .finish(&mut df)?;
+        data_files.push(DataFile { filename });
+    }
+
+    let mut info = dataset.info.clone();
+    if info.features.is_none() {
+        info.features = Some(features_from_schema(&dataset.data.schema()));
+    }
+    serde_json::to_writer_pretty(BufWriter::new(File::create(dir.join(DATASET_INFO_FILE))?), &info)?;
+
+    let state = DatasetState {
+        data_files,
+        fingerprint: dataset.fingerprint()?,
+        format_columns: None,
+        format_kwargs: serde_json::Map::new(),
+        format_type: None,
+        output_all_columns: false,
+        split: split.map(str::to_string),
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(dir.join(STATE_FILE))?), &state)?;
+    Ok(())
+}
+
+/// Write one dataset directory per split plus a `dataset_dict.json`.
+pub fn save_dict_to_disk<P: AsRef<Path>>(
+    splits: &DatasetDict,
+    dir: P,
+    num_shards: usize,
+) -> Result<(), Box<dyn Error>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    for (split, dataset) in splits.iter() {
+        write_dataset_dir(dataset, &dir.join(split), num_shards, Some(split))?;
+    }
+
+    let dict = DatasetDictState { splits: splits.split_names().into_iter().map(str::to_string).collect() };
+    serde_json::to_writer(BufWriter::new(File::create(dir.join(DATASET_DICT_FILE))?), &dict)?;
+    Ok(())
+}
+
+impl DataSet {
+    /// Load a single dataset written by Hugging Face `Dataset.save_to_disk`.
+    pub fn load_from_disk<P: AsRef<Path>>(dir: P) -> Result<DataSet, Box<dyn Error>> {
+        match load_from_disk(dir)? {
+            DiskDataset::Single(dataset) => Ok(*dataset),
+            DiskDataset::Dict(_) => Err("Directory holds a DatasetDict; use DatasetDict::load_from_disk".into()),
+        }
+    }
+
+    /// Save the dataset in the Hugging Face `save_to_disk` layout.
+    pub fn save_to_disk<P: AsRef<Path>>(&self, dir: P, num_shards: usize) -> Result<(), Box<dyn Error>> {
+        save_dataset_dir(self, dir, num_shards)
+    }
+}
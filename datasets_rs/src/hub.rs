@@ -0,0 +1,264 @@
+//! # Local Hub Cache
+//!
+//! This module resolves repo-style identifiers such as `org/name` against a local dataset cache,
+//! so air-gapped machines can load datasets from a pre-populated mirror. The cache root is taken
+//! from `DATASETS_RS_HOME`, then `HF_HOME`, and defaults to `~/.cache/huggingface`. Below it a
+//! dataset repo is looked up in two layouts:
+//!
+//! - a plain mirror at `<root>/datasets/<org>/<name>/`;
+//! - the Hugging Face hub cache at `<root>/hub/datasets--<org>--<name>/snapshots/<revision>/`,
+//!   using the revision recorded in `refs/main`.
+//!
+//! The splits of each config come from the `configs` section of the repo's `README.md` YAML
+//! header. Without it, splits are inferred from file and directory names (`train`, `test`,
+//! `validation`, ...), with every file going to `train` if none match. Patterns may also be
+//! `https://` URLs, which are fetched through the download cache.
+
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache;
+use crate::csv_options::CsvOptions;
+use crate::dataset_dict::DatasetDict;
+use crate::download;
+use crate::load_dataset::DataSet;
+use crate::multi_file::LoadManyOptions;
+use crate::sniff;
+
+/// Environment variable overriding the cache root.
+pub const CACHE_ENV: &str = "DATASETS_RS_HOME";
+
+/// Name of the config used when a repo does not declare any.
+pub const DEFAULT_CONFIG: &str = "default";
+
+/// Words that put a file into a split when no `configs` are declared.
+const SPLIT_KEYWORDS: [(&str, &[&str]); 3] = [
+    ("train", &["train", "training"]),
+    ("validation", &["validation", "valid", "val", "dev"]),
+    ("test", &["test", "testing", "eval", "evaluation"]),
+];
+
+/// One config of a dataset repo: its name and the data file patterns of each split.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoConfig {
+    pub name: String,
+    pub default: bool,
+    /// Split name to glob patterns, relative to the repo directory.
+    pub data_files: BTreeMap<String, Vec<String>>,
+}
+
+/// The cache root, from `DATASETS_RS_HOME`, `HF_HOME` or `~/.cache/huggingface`.
+pub fn cache_root() -> PathBuf {
+    if let Some(root) = env::var_os(CACHE_ENV).or_else(|| env::var_os("HF_HOME")) {
+        return PathBuf::from(root);
+    }
+    let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    home.join(".cache").join("huggingface")
+}
+
+/// Returns `true` if `name` looks like `org/name` (or a bare `name`) rather than a file path.
+pub fn is_repo_id(name: &str) -> bool {
+    let parts: Vec<&str> = name.split('/').collect();
+    parts.len() <= 2
+        && parts.iter().all(|p| {
+            !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) && !p.starts_with('.')
+        })
+        && sniff::from_extension(Path::new(name)).is_none()
+}
+
+/// Find the directory holding the files of `repo_id` below `root`.
+pub fn resolve_repo_dir(root: &Path, repo_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let mirror = root.join("datasets").join(repo_id);
+    if mirror.is_dir() {
+        return Ok(mirror);
+    }
+
+    let cached = root.join("hub").join(format!("datasets--{}", repo_id.replace('/', "--")));
+    let refs = cached.join("refs").join("main");
+    if refs.is_file() {
+        let revision = fs::read_to_string(refs)?;
+        let snapshot = cached.join("snapshots").join(revision.trim());
+        if snapshot.is_dir() {
+            return Ok(snapshot);
+        }
+    }
+
+    Err(format!("Dataset {} not found in cache {}", repo_id, root.display()).into())
+}
+
+/// Read a YAML value that is either one pattern or a list of patterns.
+fn patterns(value: &Value) -> Result<Vec<String>, Box<dyn Error>> {
+    match value {
+        Value::String(s) => Ok(vec![s.clone()]),
+        Value::Sequence(items) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string).ok_or_else(|| "data_files patterns must be strings".into()))
+            .collect(),
+        _ => Err("data_files must be a pattern, a list of patterns or a list of splits".into()),
+    }
+}
+
+/// Normalize the `data_files` of a config into split name to patterns.
+fn parse_data_files(value: &Value) -> Result<BTreeMap<String, Vec<String>>, Box<dyn Error>> {
+    let mut data_files = BTreeMap::new();
+    match value {
+        Value::Sequence(items) if items.iter().all(Value::is_mapping) => {
+            for item in items {
+                let split = item.get("split").and_then(Value::as_str).unwrap_or("train");
+                let path = item.get("path").ok_or("data_files entry is missing its path")?;
+                data_files.entry(split.to_string()).or_insert_with(Vec::new).extend(patterns(path)?);
+            }
+        }
+        Value::Mapping(map) => {
+            for (split, path) in map {
+                let split = split.as_str().ok_or("data_files split names must be strings")?;
+                data_files.insert(split.to_string(), patterns(path)?);
+            }
+        }
+        other => {
+            data_files.insert("train".to_string(), patterns(other)?);
+        }
+    }
+    Ok(data_files)
+}
+
+/// Parse the `configs` of a repo `README.md` YAML header. Returns an empty list if there are none.
+pub fn parse_readme_configs(readme: &str) -> Result<Vec<RepoConfig>, Box<dyn Error>> {
+    let Some(rest) = readme.trim_start().strip_prefix("---") else {
+        return Ok(Vec::new());
+    };
+    let Some(end) = rest.find("\n---") else {
+        return Ok(Vec::new());
+    };
+    let header: Value = serde_yaml::from_str(&rest[..end])?;
+
+    let Some(configs) = header.get("configs").and_then(Value::as_sequence) else {
+        return Ok(Vec::new());
+    };
+    configs
+        .iter()
+        .map(|config| {
+            let name = config.get("config_name").and_then(Value::as_str).unwrap_or(DEFAULT_CONFIG);
+            let data_files = config.get("data_files").ok_or_else(|| format!("Config {} has no data_files", name))?;
+            Ok(RepoConfig {
+                name: name.to_string(),
+                default: config.get("default").and_then(Value::as_bool).unwrap_or(false),
+                data_files: parse_data_files(data_files)?,
+            })
+        })
+        .collect()
+}
+
+/// The split a file belongs to, judging by its relative path, if any.
+fn split_of(relative: &Path) -> Option<&'static str> {
+    let words: Vec<String> = relative
+        .to_string_lossy()
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(str::to_string)
+        .collect();
+    SPLIT_KEYWORDS
+        .iter()
+        .find(|(_, keywords)| words.iter().any(|w| keywords.contains(&w.as_str())))
+        .map(|(split, _)| *split)
+}
+
+/// Collect the data files below `dir`, skipping hidden files and directories.
+fn data_files_below(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            data_files_below(&path, files)?;
+        } else if sniff::from_extension(&path).is_some() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Infer the default config of a repo without `configs` from its file names.
+pub fn infer_config(repo_dir: &Path) -> Result<RepoConfig, Box<dyn Error>> {
+    let mut files = Vec::new();
+    data_files_below(repo_dir, &mut files)?;
+    files.sort();
+
+    let mut by_split: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut unmatched = Vec::new();
+    for file in files {
+        let relative = file.strip_prefix(repo_dir)?.to_string_lossy().to_string();
+        match split_of(Path::new(&relative)) {
+            Some(split) => by_split.entry(split.to_string()).or_default().push(relative),
+            None => unmatched.push(relative),
+        }
+    }
+    if by_split.is_empty() {
+        by_split.insert("train".to_string(), unmatched);
+    }
+    if by_split.values().all(Vec::is_empty) {
+        return Err(format!("No data files found in {}", repo_dir.display()).into());
+    }
+
+    Ok(RepoConfig { name: DEFAULT_CONFIG.to_string(), default: true, data_files: by_split })
+}
+
+/// The configs of a repo, declared in its README or inferred from its files.
+pub fn repo_configs(repo_dir: &Path) -> Result<Vec<RepoConfig>, Box<dyn Error>> {
+    let readme = repo_dir.join("README.md");
+    if readme.is_file() {
+        let configs = parse_readme_configs(&fs::read_to_string(readme)?)?;
+        if !configs.is_empty() {
+            return Ok(configs);
+        }
+    }
+    Ok(vec![infer_config(repo_dir)?])
+}
+
+/// Pick `config` by name, or the default config when `None`.
+pub fn select_config(configs: Vec<RepoConfig>, config: Option<&str>) -> Result<RepoConfig, Box<dyn Error>> {
+    let names: Vec<String> = configs.iter().map(|c| c.name.clone()).collect();
+    let position = match config {
+        Some(name) => configs.iter().position(|c| c.name == name),
+        None => configs
+            .iter()
+            .position(|c| c.default)
+            .or_else(|| configs.iter().position(|c| c.name == DEFAULT_CONFIG))
+            .or(if configs.len() == 1 { Some(0) } else { None }),
+    };
+    match position {
+        Some(i) => Ok(configs.into_iter().nth(i).expect("position is in range")),
+        None => Err(format!("Choose a config among {:?}", names).into()),
+    }
+}
+
+/// Load every split of a config of `repo_id` from the local cache.
+pub fn load_from_cache(
+    repo_id: &str,
+    config: Option<&str>,
+    csv_options: &CsvOptions,
+) -> Result<DatasetDict, Box<dyn Error>> {
+    let root = cache_root();
+    let repo_dir = resolve_repo_dir(&root, repo_id)?;
+    let config = select_config(repo_configs(&repo_dir)?, config)?;
+    log::info!("Loading {} (config {}) from {}", repo_id, config.name, repo_dir.display());
+
+    let options = LoadManyOptions { csv: csv_options.clone(), ..Default::default() };
+    let mut splits = DatasetDict::new();
+    for (split, patterns) in &config.data_files {
+        let sources: Vec<String> = patterns
+            .iter()
+            .map(|p| if download::is_url(p) { p.clone() } else { repo_dir.join(p).to_string_lossy().to_string() })
+            .collect();
+        let mut dataset = DataSet::new(DataSet::load_many(&sources, &options)?);
+        dataset.fingerprint = cache::source_fingerprint(&sources, csv_options)?;
+        splits.insert(split.clone(), dataset);
+    }
+    splits.sort_splits();
+    Ok(splits)
+}
@@ -0,0 +1,351 @@
+//! # Concatenating and Interleaving
+//!
+//! This module mixes several datasets, such as firewall logs, IDS alerts and synthetic attacks,
+//! into one. `concatenate_datasets` stacks them; `interleave_datasets` alternates their rows,
+//! either in turn or drawn with given probabilities, like Hugging Face `interleave_datasets`.
+//! `interleave_batches` does the same for streamed sources, such as `NdjsonBatches`, without
+//! loading them whole. Schemas are aligned as in `concat_frames`: missing columns are filled with
+//! nulls and mismatching dtypes are cast to their supertype.
+
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+
+use crate::cache::{combine_fingerprints, params_key};
+use crate::load_dataset::DataSet;
+use crate::multi_file::concat_frames;
+use crate::split::take_rows;
+
+/// When interleaving stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StoppingStrategy {
+    /// Stop as soon as a source has no rows left to give.
+    #[default]
+    FirstExhausted,
+    /// Repeat sources that run out until every source has been given in full at least once.
+    AllExhausted,
+}
+
+/// Options for `interleave_datasets` and `interleave_batches`.
+#[derive(Debug, Clone, Default)]
+pub struct InterleaveOptions {
+    /// Chance of drawing each source's next row; `None` takes one row from each source in turn.
+    /// The probabilities are normalized, so they only need to be in the right proportions.
+    pub probabilities: Option<Vec<f64>>,
+    /// Seed for drawing sources by `probabilities`; the same seed gives the same order.
+    pub seed: u64,
+    pub stopping_strategy: StoppingStrategy,
+}
+
+/// The first row of each part when parts of `lengths` rows are stacked.
+fn offsets(lengths: impl Iterator<Item = usize>) -> Vec<usize> {
+    lengths
+        .scan(0, |start, len| {
+            let offset = *start;
+            *start += len;
+            Some(offset)
+        })
+        .collect()
+}
+
+/// Chooses the source of each next row.
+struct Picker {
+    /// Cumulative probabilities, or `None` for turns.
+    cumulative: Option<Vec<f64>>,
+    rng: StdRng,
+    turn: usize,
+    sources: usize,
+}
+
+impl Picker {
+    fn new(sources: usize, options: &InterleaveOptions) -> Result<Self, Box<dyn Error>> {
+        if sources == 0 {
+            return Err("Nothing to interleave".into());
+        }
+        let cumulative = match &options.probabilities {
+            None => None,
+            Some(probabilities) => {
+                if probabilities.len() != sources {
+                    return Err(format!("Got {} probabilities for {} sources", probabilities.len(), sources).into());
+                }
+                let valid = probabilities.iter().all(|p| p.is_finite() && *p >= 0.0) && probabilities.iter().sum::<f64>() > 0.0;
+                if !valid {
+                    return Err(format!("Probabilities must be non-negative with a positive sum, got {:?}", probabilities).into());
+                }
+                // A source never drawn is never exhausted, so all_exhausted would not stop.
+                if options.stopping_strategy == StoppingStrategy::AllExhausted && probabilities.contains(&0.0) {
+                    return Err("Every source needs a positive probability to stop at all_exhausted".into());
+                }
+                Some(
+                    probabilities
+                        .iter()
+                        .scan(0.0, |total, p| {
+                            *total += p;
+                            Some(*total)
+                        })
+                        .collect(),
+                )
+            }
+        };
+        Ok(Picker { cumulative, rng: StdRng::seed_from_u64(options.seed), turn: 0, sources })
+    }
+
+    fn pick(&mut self) -> usize {
+        match &self.cumulative {
+            None => {
+                let source = self.turn;
+                self.turn = (self.turn + 1) % self.sources;
+                source
+            }
+            Some(cumulative) => {
+                let draw = self.rng.gen::<f64>() * cumulative[cumulative.len() - 1];
+                cumulative.partition_point(|total| *total <= draw).min(self.sources - 1)
+            }
+        }
+    }
+}
+
+/// `data` mixed from the rows of `first` and other datasets, keeping the `info` of `first` without
+/// its split and, if `data` has another schema, its features.
+fn mixed_from(first: &DataSet, data: DataFrame) -> DataSet {
+    let schema_changed = data.schema() != first.data.schema();
+    let mut mixed = first.with_data(data);
+    mixed.info.split = None;
+    if schema_changed {
+        mixed.info.features = None;
+    }
+    mixed
+}
+
+/// Stack `datasets` into one, aligning their schemas.
+///
+/// The result keeps the `info` of the first dataset, without its split and, if aligning changed
+/// the schema, its features. It is fingerprinted from the fingerprints of all datasets, so it has
+/// none if one of them has none.
+pub fn concatenate_datasets(datasets: &[DataSet]) -> Result<DataSet, Box<dyn Error>> {
+    let Some(first) = datasets.first() else {
+        return Err("Nothing to concatenate".into());
+    };
+    let data = concat_frames(datasets.iter().map(|dataset| dataset.data.clone()).collect())?;
+    let mut concatenated = mixed_from(first, data);
+    concatenated.fingerprint = combine_fingerprints("concatenate_datasets", "", datasets);
+    Ok(concatenated)
+}
+
+/// Alternate the rows of `datasets`, in turn or drawn with `options.probabilities`.
+///
+/// Each dataset's rows are given in order. With `FirstExhausted` the result stops when a dataset
+/// runs out; with `AllExhausted` datasets that run out start over until all have been given in
+/// full, oversampling the smaller ones.
+///
+/// As with `concatenate_datasets`, the result keeps the `info` of the first dataset and is
+/// fingerprinted from the fingerprints of all datasets, along with `options`.
+pub fn interleave_datasets(datasets: &[DataSet], options: &InterleaveOptions) -> Result<DataSet, Box<dyn Error>> {
+    let mut picker = Picker::new(datasets.len(), options)?;
+    let lengths: Vec<usize> = datasets.iter().map(|dataset| dataset.data.height()).collect();
+    if options.stopping_strategy == StoppingStrategy::AllExhausted && lengths.contains(&0) {
+        return Err("Cannot repeat an empty dataset until all are exhausted".into());
+    }
+    let offsets = offsets(lengths.iter().copied());
+
+    let mut positions = vec![0; datasets.len()];
+    let mut exhausted = vec![false; datasets.len()];
+    let mut rows = Vec::new();
+    loop {
+        let source = picker.pick();
+        if positions[source] == lengths[source] {
+            break;
+        }
+        rows.push((offsets[source] + positions[source]) as IdxSize);
+        positions[source] += 1;
+        if options.stopping_strategy == StoppingStrategy::AllExhausted && positions[source] == lengths[source] {
+            exhausted[source] = true;
+            positions[source] = 0;
+            if exhausted.iter().all(|done| *done) {
+                break;
+            }
+        }
+    }
+
+    let data = concat_frames(datasets.iter().map(|dataset| dataset.data.clone()).collect())?;
+    let mut interleaved = mixed_from(&datasets[0], take_rows(&data, rows)?);
+    if options.probabilities.is_some() {
+        interleaved.info.seed = Some(options.seed);
+    }
+    let stopping_strategy = match options.stopping_strategy {
+        StoppingStrategy::FirstExhausted => "first_exhausted",
+        StoppingStrategy::AllExhausted => "all_exhausted",
+    };
+    let params = params_key(&(&options.probabilities, options.seed, stopping_strategy));
+    interleaved.fingerprint = combine_fingerprints("interleave_datasets", &params, datasets);
+    Ok(interleaved)
+}
+
+/// One streamed source being interleaved.
+struct StreamSource<I> {
+    batches: I,
+    current: Option<DataFrame>,
+    position: usize,
+    /// Batches read so far, kept to repeat the source under `AllExhausted`.
+    seen: Option<Vec<DataFrame>>,
+    replay: usize,
+    ended: bool,
+}
+
+impl<I: Iterator<Item = Result<DataFrame, Box<dyn Error>>>> StreamSource<I> {
+    /// Move to the next non-empty batch; `Ok(false)` once the source has no more rows.
+    fn advance(&mut self) -> Result<bool, Box<dyn Error>> {
+        self.position = 0;
+        loop {
+            let batch = if self.ended {
+                let seen = self.seen.as_ref().map_or(&[][..], Vec::as_slice);
+                if self.replay == seen.len() {
+                    self.current = None;
+                    return Ok(false);
+                }
+                self.replay += 1;
+                seen[self.replay - 1].clone()
+            } else {
+                match self.batches.next().transpose()? {
+                    Some(batch) => {
+                        if let Some(seen) = self.seen.as_mut() {
+                            seen.push(batch.clone());
+                        }
+                        batch
+                    }
+                    None => {
+                        self.ended = true;
+                        self.current = None;
+                        return Ok(false);
+                    }
+                }
+            };
+            if batch.height() > 0 {
+                self.current = Some(batch);
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Start over from the first batch, replaying the batches kept in `seen`.
+    fn restart(&mut self) -> Result<bool, Box<dyn Error>> {
+        self.replay = 0;
+        self.advance()
+    }
+}
+
+/// An iterator over the batches of interleaved streamed sources; see `interleave_batches`.
+pub struct InterleavedBatches<I> {
+    sources: Vec<StreamSource<I>>,
+    picker: Picker,
+    stopping_strategy: StoppingStrategy,
+    exhausted: Vec<bool>,
+    batch_size: usize,
+    started: bool,
+    done: bool,
+}
+
+/// Interleave streamed sources of `DataFrame` batches, yielding batches of `batch_size` rows.
+///
+/// Rows are chosen as by `interleave_datasets`. Sources are read a batch at a time, except that
+/// `AllExhausted` keeps the batches read so far in memory to repeat the sources that run out.
+pub fn interleave_batches<I>(
+    sources: Vec<I>,
+    options: &InterleaveOptions,
+    batch_size: usize,
+) -> Result<InterleavedBatches<I>, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<DataFrame, Box<dyn Error>>>,
+{
+    let picker = Picker::new(sources.len(), options)?;
+    let keep = options.stopping_strategy == StoppingStrategy::AllExhausted;
+    let sources: Vec<StreamSource<I>> = sources
+        .into_iter()
+        .map(|batches| StreamSource {
+            batches,
+            current: None,
+            position: 0,
+            seen: keep.then(Vec::new),
+            replay: 0,
+            ended: false,
+        })
+        .collect();
+    Ok(InterleavedBatches {
+        exhausted: vec![false; sources.len()],
+        sources,
+        picker,
+        stopping_strategy: options.stopping_strategy,
+        batch_size: batch_size.max(1),
+        started: false,
+        done: false,
+    })
+}
+
+impl<I: Iterator<Item = Result<DataFrame, Box<dyn Error>>>> InterleavedBatches<I> {
+    fn next_batch(&mut self) -> Result<Option<DataFrame>, Box<dyn Error>> {
+        if !self.started {
+            self.started = true;
+            for source in &mut self.sources {
+                if !source.advance()? && self.stopping_strategy == StoppingStrategy::AllExhausted {
+                    return Err("Cannot repeat an empty source until all are exhausted".into());
+                }
+            }
+        }
+
+        // The batches rows are taken from, and for each source the one it is reading, if taken yet.
+        let mut frames: Vec<DataFrame> = Vec::new();
+        let mut frame_of: Vec<Option<usize>> = vec![None; self.sources.len()];
+        let mut picks: Vec<(usize, usize)> = Vec::with_capacity(self.batch_size);
+        while picks.len() < self.batch_size {
+            let index = self.picker.pick();
+            let source = &mut self.sources[index];
+            let Some(current) = &source.current else {
+                self.done = true;
+                break;
+            };
+            let frame = *frame_of[index].get_or_insert_with(|| {
+                frames.push(current.clone());
+                frames.len() - 1
+            });
+            picks.push((frame, source.position));
+            source.position += 1;
+
+            if source.position == current.height() {
+                frame_of[index] = None;
+                if !source.advance()? && self.stopping_strategy == StoppingStrategy::AllExhausted {
+                    self.exhausted[index] = true;
+                    source.restart()?;
+                    if self.exhausted.iter().all(|done| *done) {
+                        self.done = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if picks.is_empty() {
+            return Ok(None);
+        }
+
+        let offsets = offsets(frames.iter().map(DataFrame::height));
+        let rows = picks.into_iter().map(|(frame, row)| (offsets[frame] + row) as IdxSize).collect();
+        Ok(Some(take_rows(&concat_frames(frames)?, rows)?))
+    }
+}
+
+impl<I: Iterator<Item = Result<DataFrame, Box<dyn Error>>>> Iterator for InterleavedBatches<I> {
+    type Item = Result<DataFrame, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_batch() {
+            Ok(batch) => batch.map(Ok),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! # Lazy DataSet
+//!
+//! This module provides `LazyDataSet`, a `DataSet` backed by a Polars `LazyFrame`. Scanning a
+//! file only reads its schema; transforms, splits and saves are recorded as a query plan, so
+//! Polars can push projections and predicates down into the CSV, NDJSON, Parquet and Arrow IPC
+//! readers. Data is only materialized when the dataset is collected or streamed into a file.
+
+use polars::prelude::*;
+use std::error::Error;
+use std::path::Path;
+use uuid::Uuid;
+use chrono::Local;
+
+use crate::compression::Compression;
+use crate::csv_options::CsvOptions;
+use crate::load_dataset::{DataSet, DatasetInfo};
+use crate::sniff;
+use crate::split::SplitInfo;
+
+/// Name of the column holding the index of each row while splitting.
+const ROW_COLUMN: &str = "__split_row";
+
+/// Number of buckets rows are hashed into by a seeded split; `test_ratio` of them go to the test set.
+const SPLIT_BUCKETS: u64 = 1_000_000;
+
+/// The bucket of row `row` for a split seeded with `seed`, from the SplitMix64 finalizer.
+fn split_bucket(seed: u64, row: u64) -> u64 {
+    let mut z = seed.wrapping_add(row.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) % SPLIT_BUCKETS
+}
+
+/// A `DataSet` whose data is a `LazyFrame`; nothing is read until it is collected or saved.
+#[derive(Clone)]
+pub struct LazyDataSet {
+    pub data: LazyFrame,
+    pub uuid: Uuid,
+    pub timestamp: String,
+    pub info: DatasetInfo,
+}
+
+impl LazyDataSet {
+    /// Create a new `LazyDataSet` instance.
+    pub fn new(data: LazyFrame) -> Self {
+        let uuid = Uuid::new_v4();
+        let timestamp = Local::now().format("%d-%m-%y-%H").to_string();
+        LazyDataSet { data, uuid, timestamp, info: DatasetInfo::default() }
+    }
+
+    /// Scan a CSV, NDJSON, Parquet or Arrow IPC file without reading its rows.
+    pub fn scan<P: AsRef<Path>>(file_path: P) -> Result<Self, Box<dyn Error>> {
+        Self::scan_with(file_path, &CsvOptions::default())
+    }
+
+    /// Scan a file like `scan`, reading CSV input with the given dialect options.
+    pub fn scan_with<P: AsRef<Path>>(file_path: P, csv_options: &CsvOptions) -> Result<Self, Box<dyn Error>> {
+        let path = file_path.as_ref();
+        let detected = sniff::detect_format(path)?;
+        if detected.compression != Compression::None {
+            return Err(format!("Cannot scan compressed file {} lazily; use DataSet::load_data", path.display()).into());
+        }
+
+        let lf = match detected.format {
+            "csv" => csv_options.scan_file(path, detected.separator)?,
+            "jsonl" => LazyJsonLineReader::new(path).finish()?,
+            "parquet" => LazyFrame::scan_parquet(path, ScanArgsParquet::default())?,
+            "arrow" => LazyFrame::scan_ipc(path, ScanArgsIpc::default())?,
+            other => return Err(format!("Unsupported file format for lazy scan: {}", other).into()),
+        };
+
+        Ok(LazyDataSet::new(lf))
+    }
+
+    /// Replace the query plan while keeping the dataset's `uuid`, `timestamp` and `info`.
+    fn with_data(&self, data: LazyFrame) -> Self {
+        LazyDataSet { data, uuid: self.uuid, timestamp: self.timestamp.clone(), info: self.info.clone() }
+    }
+
+    /// Keep only the given columns; unused columns are never read from disk.
+    pub fn select<E: AsRef<[Expr]>>(&self, exprs: E) -> Self {
+        self.with_data(self.data.clone().select(exprs))
+    }
+
+    /// Keep the rows matching `predicate`; the predicate is pushed down into the scan.
+    pub fn filter(&self, predicate: Expr) -> Self {
+        self.with_data(self.data.clone().filter(predicate))
+    }
+
+    /// Add or replace columns with the given expressions.
+    pub fn with_columns<E: AsRef<[Expr]>>(&self, exprs: E) -> Self {
+        self.with_data(self.data.clone().with_columns(exprs))
+    }
+
+    /// Take `len` rows starting at `offset`.
+    pub fn slice(&self, offset: i64, len: IdxSize) -> Self {
+        self.with_data(self.data.clone().slice(offset, len))
+    }
+
+    /// Count the rows of the query without collecting the columns.
+    pub fn height(&self) -> Result<usize, Box<dyn Error>> {
+        let counts = self.data.clone().select([len()]).collect()?;
+        let height = counts.column("len")?.idx()?.get(0).unwrap_or(0);
+        Ok(height as usize)
+    }
+
+    /// Split the dataset into a train and a test part, keeping both lazy.
+    ///
+    /// Without a seed, the last `test_ratio` of the rows form the test set, and only the row count
+    /// is read to plan the split. With a seed, each row goes to the test set if a hash of the seed
+    /// and its index falls in the first `test_ratio` of the buckets, so the split streams without
+    /// holding a shuffled order in memory. The same seed gives the same splits, but unlike
+    /// `DataSet::train_test_split` the rows keep their order and the test set only holds about
+    /// `test_ratio` of them.
+    pub fn train_test_split(&self, test_ratio: f32, seed: Option<u64>) -> Result<(Self, Self), Box<dyn Error>> {
+        if !(0.0..=1.0).contains(&test_ratio) {
+            return Err(format!("test_ratio must be between 0 and 1, got {}", test_ratio).into());
+        }
+        let mut split = self.clone();
+        split.info.split =
+            Some(SplitInfo { method: "train_test_split".to_string(), test_ratio, seed, ..Default::default() });
+
+        let Some(seed) = seed else {
+            let n = self.height()?;
+            let test_size = (n as f32 * test_ratio).round() as usize;
+            let train_size = n - test_size;
+            return Ok((split.slice(0, train_size as IdxSize), split.slice(train_size as i64, test_size as IdxSize)));
+        };
+        let threshold = (f64::from(test_ratio) * SPLIT_BUCKETS as f64).round() as u64;
+        let in_test = col(ROW_COLUMN).map(
+            move |rows| {
+                let in_test: BooleanChunked =
+                    rows.idx()?.into_no_null_iter().map(|row| split_bucket(seed, row as u64) < threshold).collect();
+                Ok(Some(in_test.into_series()))
+            },
+            GetOutput::from_type(DataType::Boolean),
+        );
+        let indexed = self.data.clone().with_row_index(ROW_COLUMN, None);
+        let train = indexed.clone().filter(in_test.clone().not()).drop([ROW_COLUMN]);
+        let test = indexed.filter(in_test).drop([ROW_COLUMN]);
+        Ok((split.with_data(train), split.with_data(test)))
+    }
+
+    /// Run the query and materialize the result as a `DataSet`.
+    pub fn collect(&self) -> Result<DataSet, Box<dyn Error>> {
+        let data = self.data.clone().collect()?;
+        let info = self.info.clone();
+        Ok(DataSet { data, uuid: self.uuid, timestamp: self.timestamp.clone(), info, fingerprint: None })
+    }
+
+    /// Stream the query result into a CSV, Parquet or Arrow IPC file without collecting it in memory.
+    ///
+    /// Other formats, and queries the streaming engine cannot run, are collected first and written
+    /// through `DataSet::save_data`.
+    pub fn save_data<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        file_extension: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = file_path.as_ref();
+        let streamed = match file_extension {
+            "csv" => self.data.clone().sink_csv(path, CsvWriterOptions::default()),
+            "parquet" => self.data.clone().sink_parquet(path, ParquetWriteOptions::default()),
+            "arrow" | "feather" | "ipc" => self.data.clone().sink_ipc(path, IpcWriterOptions::default()),
+            _ => return self.collect()?.save_data(path, file_extension),
+        };
+
+        if let Err(e) = streamed {
+            log::warn!("Cannot stream into {}: {}; collecting first", path.display(), e);
+            self.collect()?.save_data(path, file_extension)?;
+        }
+        Ok(())
+    }
+}
@@ -6,13 +6,85 @@
 //! ## Features
 //! 
 //! - **Concurrent DataFrame Handling**: Uses `Polars` for efficient DataFrame processing.
-//! - **Multiple Format Support**: Supports `.csv`, `.json`, and `.parquet` formats.
+//! - **Multiple Format Support**: Supports `.csv`, `.json`, `.jsonl`, `.parquet` and Arrow IPC
+//!   (`.arrow`/`.feather` files and the `.arrows` streaming format).
 //! 
 //! ## Usage
 //!
 //! ### Loading and Saving
 //! The core module provides functions for loading datasets from CSV, JSON, and Parquet formats,
 //! and saving them into one of these formats based on user input.
+//!
+//! ### Lazy Scanning
+//! `DataSet::scan` and `DataSet::scan_with`, which takes a CSV dialect, return a `LazyDataSet`
+//! backed by a Polars `LazyFrame`, so filters and column selections are pushed down into the
+//! reader and rows are only read when collected or saved.
+//!
+//! ### Columns
+//! `DataSet::rename_columns`, `remove_columns`, `select_columns`, `cast_column`, `add_column` and
+//! `flatten` manage the columns of a dataset, naming the available columns when one is missing.
+//!
+//! ### Mapping
+//! `DataSet::map` and `DataSet::map_batches` run a closure over every row or batch of rows in
+//! parallel with rayon, adding, replacing or removing columns.
+//!
+//! ### Selecting Rows
+//! `DataSet::filter`, `select`, `take`, `sort`, `unique` and `sample_frac` return new datasets;
+//! `DataSet::view` chains the same operations on row indices without copying any column.
+//!
+//! ### Caching
+//! Datasets loaded from files carry a fingerprint of their content, which each transform, such as
+//! `select`, `sort`, `shuffle` or a `map` with a `cache_key`, derives anew from its parameters.
+//! Results are cached by fingerprint as Arrow files and reused when a pipeline runs again; see
+//! [`cache`] to disable or clear the cache.
+//!
+//! ### Splits
+//! `DataSet::train_test_split` and `load_dataset` return a `DatasetDict` mapping split names to
+//! `DataSet`s, which can be transformed, summarized and saved as a whole.
+//! `DataSet::stratified_train_test_split` keeps the class proportions of a target column in both.
+//! `DataSet::train_test_split_by_group` keeps all rows of a group, such as a host, in one split.
+//! `DataSet::split` makes any number of named splits, such as `train`, `validation` and `test`.
+//! `load_dataset_split` and `DatasetDict::select_split` take Hugging Face split expressions
+//! such as `train[:10%]+test[50%:]`.
+//! `DataSet::train_test_split_at` and `DataSet::train_test_split_by_time` split chronologically,
+//! and `DataSet::rolling_origin` yields backtesting folds that always validate on later rows.
+//! Shuffling, sampling, splitting and the `DataLoader` take explicit seeds, which are recorded
+//! in each dataset's `info` so a split can be regenerated.
+//! `DataSet::k_fold` and its stratified, repeated, group and leave-one-group-out variants yield
+//! `(train, validation)` views for cross-validation, which convert to `ndarray`s for linfa.
+//!
+//! ### Mixing Datasets
+//! `interleave::concatenate_datasets` stacks datasets with differing schemas, and
+//! `interleave::interleave_datasets` mixes their rows in turn or by sampling probabilities, with
+//! `interleave_batches` doing the same for streamed sources.
+//!
+//! ### Sharding
+//! `DataSet::shard` picks one of `num_shards` shards, contiguous or strided as in Hugging Face.
+//! `DataSet::save_shards` writes all shards with a `shards.json` manifest, and each worker reads
+//! its own with `DataSet::load_shard`.
+
+pub mod cache;
+pub mod columns;
+pub mod compression;
+pub mod cross_validation;
+pub mod csv_options;
+pub mod dataloader;
+pub mod dataset_dict;
+pub mod download;
+pub mod hf_disk;
+pub mod hub;
+pub mod interleave;
+pub mod lazy_dataset;
+pub mod load_dataset;
+pub mod map;
+pub mod multi_file;
+pub mod ndjson;
+pub mod shard;
+pub mod sniff;
+pub mod split;
+pub mod split_expr;
+pub mod time_split;
+pub mod view;
 
 use std::error::Error;
 use std::fs::OpenOptions;
@@ -23,6 +95,11 @@ use polars::prelude::*;
 use std::path::Path;
 use parquet::column::page::PageWriter;
 
+use crate::csv_options::CsvOptions;
+use crate::dataset_dict::DatasetDict;
+use crate::load_dataset::DataSet;
+use crate::split_expr::SplitExpr;
+
 
 /// Saves a DataFrame as a Parquet file.
 ///
@@ -110,16 +187,53 @@ pub fn save_as_csv<P: AsRef<Path>>(df: &DataFrame, file_path: P) -> Result<(), B
 }
 
 
-/// Loads a dataset from a CSV file and returns a DataFrame.
+/// Loads a dataset and returns its splits.
+///
+/// `name` is either a data file or `https://` URL, loaded as a single `train` split, or a repo
+/// id such as `org/name`, resolved against the local hub cache (see [`hub`]). URLs are fetched
+/// through the download cache (see [`download`]). `config` picks one of the repo's configs;
+/// `None` uses its default config. CSV files are read with `csv_options`.
+///
+/// # Example
+///
+/// ```rust
+/// let splits = load_dataset("input.csv", None, &CsvOptions::default())?;
+/// let splits = load_dataset("https://example.com/flows.parquet", None, &CsvOptions::default())?;
+/// let splits = load_dataset("acme/firewall-logs", Some("2026"), &CsvOptions::default())?;
+/// ```
+pub fn load_dataset(
+    name: &str,
+    config: Option<&str>,
+    csv_options: &CsvOptions,
+) -> Result<DatasetDict, Box<dyn Error>> {
+    let path = Path::new(name);
+    if !path.exists() && hub::is_repo_id(name) {
+        return hub::load_from_cache(name, config, csv_options);
+    }
+
+    let mut dataset = DataSet::new(DataSet::load_data_with(path, csv_options)?);
+    dataset.fingerprint = cache::source_fingerprint(&[name], csv_options)?;
+    let mut splits = DatasetDict::new();
+    splits.insert("train", dataset);
+    Ok(splits)
+}
+
+/// Loads the rows of a dataset named by a split expression, such as `train[:10%]+test[50%:]`.
+///
+/// `name`, `config` and `csv_options` are as for [`load_dataset`]; see [`split_expr`] for the
+/// syntax. The expression is checked before anything is loaded.
 ///
 /// # Example
 ///
 /// ```rust
-/// let df = load_dataset("input.csv")?;
+/// let subset = load_dataset_split("acme/firewall-logs", None, "train[:10%]", &CsvOptions::default())?;
 /// ```
-pub fn load_dataset<P: AsRef<std::path::Path>>(file_path: P) -> Result<DataFrame, Box<dyn Error>> {
-    let file = File::open(file_path)?;
-    let df = CsvReader::new(file)
-        .finish()?;
-    Ok(df)
+pub fn load_dataset_split(
+    name: &str,
+    config: Option<&str>,
+    split: &str,
+    csv_options: &CsvOptions,
+) -> Result<DataSet, Box<dyn Error>> {
+    let expr = SplitExpr::parse(split)?;
+    expr.apply(&load_dataset(name, config, csv_options)?)
 }
@@ -11,10 +11,19 @@ use serde::{Deserialize, Serialize};
 use std::fs::{File, OpenOptions};
 use std::sync::Arc;
 use std::error::Error;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufReader, BufWriter, Cursor, Read};
 use uuid::Uuid;
 use chrono::Local;
 
+use crate::compression::{self, CompressedWriter, Compression};
+use crate::csv_options::CsvOptions;
+use crate::download::{self, DownloadOptions};
+use crate::lazy_dataset::LazyDataSet;
+use crate::multi_file::LoadManyOptions;
+use crate::ndjson;
+use crate::sniff::{self, SniffedFormat};
+use crate::split::SplitInfo;
+
 /// A structure that represents a single record in a security dataset.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SecurityRecord {
@@ -26,11 +35,56 @@ pub struct SecurityRecord {
     pub protocol: String,
 }
 
+impl SecurityRecord {
+    /// Build a `DataFrame` with one column per `SecurityRecord` field.
+    pub fn to_dataframe(records: &[SecurityRecord]) -> Result<DataFrame, Box<dyn Error>> {
+        let df = DataFrame::new(vec![
+            Series::new("uuid", records.iter().map(|r| r.uuid.to_string()).collect::<Vec<String>>()),
+            Series::new("timestamp", records.iter().map(|r| r.timestamp.clone()).collect::<Vec<String>>()),
+            Series::new("source_ip", records.iter().map(|r| r.source_ip.clone()).collect::<Vec<String>>()),
+            Series::new("destination_ip", records.iter().map(|r| r.destination_ip.clone()).collect::<Vec<String>>()),
+            Series::new("action", records.iter().map(|r| r.action.clone()).collect::<Vec<String>>()),
+            Series::new("protocol", records.iter().map(|r| r.protocol.clone()).collect::<Vec<String>>()),
+        ])?;
+        Ok(df)
+    }
+}
+
+/// Descriptive metadata carried alongside a `DataSet`, mirroring Hugging Face's `dataset_info.json`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DatasetInfo {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub citation: String,
+    #[serde(default)]
+    pub homepage: String,
+    #[serde(default)]
+    pub license: String,
+    /// Hugging Face `features` describing the columns, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<serde_json::Value>,
+    /// Seed of the last shuffle or sample that produced this dataset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// How this split was produced, if the dataset is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<SplitInfo>,
+    /// Any other `dataset_info.json` keys, kept so they survive a round trip.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 /// The main `DataSet` structure used for storing and managing the dataset in a DataFrame.
+#[derive(Clone)]
 pub struct DataSet {
     pub data: DataFrame,
     pub uuid: Uuid,
     pub timestamp: String,
+    pub info: DatasetInfo,
+    /// Fingerprint from the source files and the transforms applied since, used to find cached
+    /// transform results; `None` for data built in memory (see `cache`).
+    pub fingerprint: Option<String>,
 }
 
 impl DataSet {
@@ -38,88 +92,274 @@ impl DataSet {
     pub fn new(data: DataFrame) -> Self {
         let uuid = Uuid::new_v4();
         let timestamp = Local::now().format("%d-%m-%y-%H").to_string();
-        DataSet { data, uuid, timestamp }
+        DataSet { data, uuid, timestamp, info: DatasetInfo::default(), fingerprint: None }
     }
 
-    /// Load data from a file in CSV, JSON, or Parquet format and convert it into a `DataFrame`.
+    /// Replace the data while keeping the dataset's `uuid`, `timestamp` and `info`.
+    ///
+    /// The fingerprint is dropped, since it no longer describes the data.
+    pub(crate) fn with_data(&self, data: DataFrame) -> Self {
+        let info = self.info.clone();
+        DataSet { data, uuid: self.uuid, timestamp: self.timestamp.clone(), info, fingerprint: None }
+    }
+
+    /// Scan a CSV, NDJSON, Parquet or Arrow IPC file into a `LazyDataSet` without reading its rows.
+    pub fn scan<P: AsRef<std::path::Path>>(file_path: P) -> Result<LazyDataSet, Box<dyn Error>> {
+        LazyDataSet::scan(file_path)
+    }
+
+    /// Scan a file like `scan`, reading CSV input with the given dialect options.
+    pub fn scan_with<P: AsRef<std::path::Path>>(
+        file_path: P,
+        csv_options: &CsvOptions,
+    ) -> Result<LazyDataSet, Box<dyn Error>> {
+        LazyDataSet::scan_with(file_path, csv_options)
+    }
+
+    /// Turn this dataset into a `LazyDataSet`, keeping its `uuid`, `timestamp` and `info`.
+    pub fn lazy(&self) -> LazyDataSet {
+        LazyDataSet {
+            data: self.data.clone().lazy(),
+            uuid: self.uuid,
+            timestamp: self.timestamp.clone(),
+            info: self.info.clone(),
+        }
+    }
+
+    /// Load data from a file in CSV, JSON, NDJSON, Parquet or Arrow IPC format and convert it into a `DataFrame`.
+    ///
+    /// The format is sniffed from the file content, so extensionless or mislabelled files load
+    /// correctly. Gzip, zstd, bzip2 and xz files (e.g. `events.csv.gz`) are decompressed as a
+    /// stream before parsing. A directory is loaded with `load_many`.
     pub fn load_data<P: AsRef<std::path::Path>>(file_path: P) -> Result<DataFrame, Box<dyn Error>> {
+        Self::load_data_with(file_path, &CsvOptions::default())
+    }
+
+    /// Load data like `load_data`, reading CSV input with the given dialect options.
+    pub fn load_data_with<P: AsRef<std::path::Path>>(
+        file_path: P,
+        csv_options: &CsvOptions,
+    ) -> Result<DataFrame, Box<dyn Error>> {
         let path = file_path.as_ref();
-        let ext = path.extension().and_then(std::ffi::OsStr::to_str).unwrap_or("");
-
-        let df = match ext {
-            "csv" => CsvReader::from_path(path)?.infer_schema(None).finish()?,
-            "json" => {
-                let json_file = File::open(path)?;
-                let reader = BufReader::new(json_file);
-                let json_data: Vec<_> = serde_json::from_reader(BufReader::new(json_file))?;
-                let records: Vec<SecurityRecord> = serde_json::from_reader(json_data)?;
-                
-                let df = DataFrame::new(vec![
-                    Series::new("uuid", records.iter().map(|r| r.uuid.to_string()).collect::<Vec<String>>()),
-                    Series::new("timestamp", records.iter().map(|r| r.timestamp.clone()).collect::<Vec<String>>()),
-                    Series::new("source_ip", records.iter().map(|r| r.source_ip.clone()).collect::<Vec<String>>()),
-                    Series::new("destination_ip", records.iter().map(|r| r.destination_ip.clone()).collect::<Vec<String>>()),
-                    Series::new("action", records.iter().map(|r| r.action.clone()).collect::<Vec<String>>()),
-                    Series::new("protocol", records.iter().map(|r| r.protocol.clone()).collect::<Vec<String>>()),
-                ])?;
-                df
-            }
+        if let Some(url) = path.to_str().filter(|p| download::is_url(p)) {
+            return Self::load_data_with(download::download(url, &DownloadOptions::default())?, csv_options);
+        }
+        if path.is_dir() {
+            let options = LoadManyOptions { csv: csv_options.clone(), ..Default::default() };
+            return Self::load_many(&[path.to_string_lossy()], &options);
+        }
+        Self::load_detected(path, &sniff::detect_format(path)?, csv_options)
+    }
+
+    /// Load a file as `format`, one of `sniff::FORMATS`, instead of the format sniffed from its
+    /// content; its compression and, for CSV, its delimiter are still sniffed.
+    pub fn load_data_as<P: AsRef<std::path::Path>>(
+        file_path: P,
+        format: &str,
+        csv_options: &CsvOptions,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let path = file_path.as_ref();
+        Self::load_detected(path, &sniff::sniff_path_as(path, format)?, csv_options)
+    }
+
+    /// Load a file in the `detected` format.
+    fn load_detected(
+        path: &std::path::Path,
+        detected: &SniffedFormat,
+        csv_options: &CsvOptions,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        if detected.compression != Compression::None {
+            return Self::load_compressed(path, detected, csv_options);
+        }
+
+        let df = match detected.format {
+            "csv" => csv_options.read_file(path, detected.separator)?,
+            "json" => Self::load_json(path, None)?,
+            "jsonl" => Self::load_ndjson(path)?,
             "parquet" => {
                 let df = LazyFrame::scan_parquet(path, Default::default())?.collect()?;
                 df
             }
-            _ => return Err("Unsupported file format".into()),
+            "arrow" => IpcReader::new(File::open(path)?).finish()?,
+            "arrows" => IpcStreamReader::new(BufReader::new(File::open(path)?)).finish()?,
+            other => return Err(format!("Unsupported file format: {}", other).into()),
         };
 
         Ok(df)
     }
 
+    /// Load a compressed file, decompressing it as a stream.
+    ///
+    /// NDJSON is parsed batch by batch straight from the decoder; the other formats need the whole
+    /// decompressed buffer before they can be parsed.
+    fn load_compressed(
+        path: &std::path::Path,
+        detected: &SniffedFormat,
+        csv_options: &CsvOptions,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let mut reader = compression::open_decompressed(path, detected.compression)?;
+        match detected.format {
+            "jsonl" => return ndjson::read_ndjson(reader, ndjson::DEFAULT_BATCH_SIZE),
+            "csv" => return csv_options.read(reader, detected.separator),
+            _ => {}
+        }
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let cursor = Cursor::new(buf);
+
+        let df = match detected.format {
+            "json" => JsonReader::new(cursor)
+                .with_json_format(JsonFormat::Json)
+                .infer_schema_len(None)
+                .finish()?,
+            "parquet" => ParquetReader::new(cursor).finish()?,
+            "arrow" => IpcReader::new(cursor).finish()?,
+            "arrows" => IpcStreamReader::new(cursor).finish()?,
+            other => return Err(format!("Unsupported file format: {}", other).into()),
+        };
+        Ok(df)
+    }
+
     /// Save the dataset as a Parquet file.
     pub fn save_as_parquet<P: AsRef<std::path::Path>>(df: &DataFrame, file_path: P) -> Result<(), Box<dyn Error>> {
-        let path = file_path.as_ref();
-        df.write_parquet(path, Default::default())?;
+        let file = File::create(file_path)?;
+        let mut df = df.clone();
+        ParquetWriter::new(file).finish(&mut df)?;
+        Ok(())
+    }
+
+    /// Save the dataset as an Arrow IPC (Feather v2) file.
+    ///
+    /// Strings are written as `large_string` rather than string views so older pyarrow versions
+    /// and Hugging Face `datasets` can read the file without conversion.
+    pub fn save_as_ipc<P: AsRef<std::path::Path>>(df: &DataFrame, file_path: P) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)?;
+        let mut df = df.clone();
+        IpcWriter::new(BufWriter::new(file))
+            .with_compat_level(CompatLevel::oldest())
+            .finish(&mut df)?;
         Ok(())
     }
 
-    /// Load JSON data from a file
-    fn load_json(file_path: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+    /// Save the dataset in the Arrow IPC streaming format.
+    pub fn save_as_ipc_stream<P: AsRef<std::path::Path>>(df: &DataFrame, file_path: P) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)?;
+        let mut df = df.clone();
+        IpcStreamWriter::new(BufWriter::new(file))
+            .with_compat_level(CompatLevel::oldest())
+            .finish(&mut df)?;
+        Ok(())
+    }
+
+    /// Load a JSON array of objects, inferring the schema from every record.
+    ///
+    /// Nested objects become `Struct` columns and arrays become `List` columns. Columns named in
+    /// `schema_hints` are read with the given dtype instead of the inferred one.
+    pub fn load_json<P: AsRef<std::path::Path>>(
+        file_path: P,
+        schema_hints: Option<&Schema>,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        let mut reader = JsonReader::new(file)
+            .with_json_format(JsonFormat::Json)
+            .infer_schema_len(None);
+        if let Some(hints) = schema_hints {
+            reader = reader.with_schema_overwrite(hints);
+        }
+        Ok(reader.finish()?)
+    }
+
+    /// Load a JSON array of `SecurityRecord`s, rejecting records that do not match the struct.
+    pub fn load_security_records<P: AsRef<std::path::Path>>(file_path: P) -> Result<DataFrame, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        let records: Vec<SecurityRecord> = serde_json::from_reader(BufReader::new(file))?;
+        SecurityRecord::to_dataframe(&records)
+    }
+
+    /// Load a JSON Lines file, reading it line by line in batches.
+    pub fn load_ndjson<P: AsRef<std::path::Path>>(file_path: P) -> Result<DataFrame, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        ndjson::read_ndjson(BufReader::new(file), ndjson::DEFAULT_BATCH_SIZE)
+    }
+
+    /// Iterate over a JSON Lines file in `DataFrame` batches of `batch_size` lines.
+    pub fn ndjson_batches<P: AsRef<std::path::Path>>(
+        file_path: P,
+        batch_size: usize,
+    ) -> Result<ndjson::NdjsonBatches<BufReader<File>>, Box<dyn Error>> {
         let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
-        let json_data: serde_json::Value = from_reader(reader)?;
-        Ok(json_data)
+        Ok(ndjson::NdjsonBatches::new(BufReader::new(file), batch_size))
     }
 
     /// Save the dataset as a JSON file.
     pub fn export_as_json<P: AsRef<std::path::Path>>(df: &DataFrame, file_path: P) -> Result<(), Box<dyn Error>> {
-        let json_file = File::create("output.json")?;
-        let mut file = BufWriter::new(json_file);
-        let mut writer: Box<dyn PageWriter> = PageWriter::new(file);
-
-        let json: Vec<_> = df.iter().map(|s| s.to_string()).collect(); // Convert DataFrame to rows of strings
-        let json_data = serde_json::to_string(&json)?; // Serialize to JSON string
-        writer.write_all(json_data.as_bytes())?;
+        let json_file = File::create(file_path)?;
+        let mut df = df.clone();
+        JsonWriter::new(BufWriter::new(json_file))
+            .with_json_format(JsonFormat::Json)
+            .finish(&mut df)?;
         Ok(())
     }
 
+    /// Save the dataset as a JSON Lines file.
+    pub fn save_as_ndjson<P: AsRef<std::path::Path>>(df: &DataFrame, file_path: P) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)?;
+        ndjson::write_ndjson(df, BufWriter::new(file))
+    }
+
 
     /// Save DataFrame as a CSV file
-    fn save_as_csv(df: &DataFrame, file_path: &str) -> Result<(), Box<dyn Error>> {
+    fn save_as_csv<P: AsRef<std::path::Path>>(df: &DataFrame, file_path: P) -> Result<(), Box<dyn Error>> {
         let file = File::create(file_path)?;
         let writer = BufWriter::new(file);
-        CsvWriter::new(writer).finish(df)?;
+        let mut df = df.clone();
+        CsvWriter::new(writer).finish(&mut df)?;
         Ok(())
     }
 
-    /// Save the dataset in the desired format (CSV, JSON, or Parquet).
+    /// Write CSV, JSON or NDJSON through a compressing writer.
+    fn save_compressed<P: AsRef<std::path::Path>>(
+        df: &DataFrame,
+        file_path: P,
+        format: &str,
+        compression: Compression,
+    ) -> Result<(), Box<dyn Error>> {
+        if !matches!(format, "csv" | "json" | "jsonl" | "ndjson") {
+            return Err(format!("Compressed output is not supported for {}", format).into());
+        }
+
+        let mut writer = CompressedWriter::create(file_path, compression)?;
+        let mut df = df.clone();
+        match format {
+            "csv" => CsvWriter::new(&mut writer).finish(&mut df)?,
+            "json" => JsonWriter::new(&mut writer).with_json_format(JsonFormat::Json).finish(&mut df)?,
+            _ => ndjson::write_ndjson(&df, &mut writer)?,
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Save the dataset in the desired format (CSV, JSON, NDJSON, Parquet or Arrow IPC).
+    ///
+    /// A compound extension such as `csv.gz` or `jsonl.zst` writes compressed CSV or JSON.
     pub fn save_data<P: AsRef<std::path::Path>>(
         &self,
         file_path: P,
         file_extension: &str,
     ) -> Result<(), Box<dyn Error>> {
+        let (format, compression) = compression::split_format(file_extension);
+        if compression != Compression::None {
+            return Self::save_compressed(&self.data, file_path, format, compression);
+        }
+
         match file_extension {
-            "csv" => self::save_as_csv(&self.data, file_path)?,
-            "json" => self::export_as_json(&self.data, file_path)?,
-            "parquet" => self::save_as_parquet(&self.data, file_path)?,
+            "csv" => Self::save_as_csv(&self.data, file_path)?,
+            "json" => Self::export_as_json(&self.data, file_path)?,
+            "jsonl" | "ndjson" => Self::save_as_ndjson(&self.data, file_path)?,
+            "parquet" => Self::save_as_parquet(&self.data, file_path)?,
+            "arrow" | "feather" | "ipc" => Self::save_as_ipc(&self.data, file_path)?,
+            "arrows" => Self::save_as_ipc_stream(&self.data, file_path)?,
             _ => return Err("Unsupported file format".into()),
         }
         Ok(())
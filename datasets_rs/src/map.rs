@@ -0,0 +1,273 @@
+//! # Mapping
+//!
+//! This module runs feature engineering over a `DataSet` in parallel, like Hugging Face
+//! `Dataset.map`. `DataSet::map` calls a closure on every row and `DataSet::map_batches` on
+//! every batch of rows; either can add, replace or remove columns. Batches are processed on a
+//! rayon pool and put back together in their original order, with their schemas aligned as in
+//! `concat_frames`.
+
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::error::Error;
+
+use crate::cache::params_key;
+use crate::load_dataset::DataSet;
+use crate::multi_file::concat_frames;
+
+/// Options for `DataSet::map` and `DataSet::map_batches`.
+#[derive(Debug, Clone)]
+pub struct MapOptions {
+    /// Rows per batch; `map` also hands rows to the workers in batches of this size.
+    pub batch_size: usize,
+    /// Threads to run on; 0 uses rayon's global pool.
+    pub num_workers: usize,
+    /// Columns to drop from the result, such as the raw fields a feature was derived from.
+    pub remove_columns: Vec<String>,
+    /// Name and version of the closure, such as `port_features-v2`, to fingerprint and cache the
+    /// result by (see `cache`); change it whenever the closure changes. `None` never caches.
+    pub cache_key: Option<String>,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        MapOptions { batch_size: 1000, num_workers: 0, remove_columns: Vec::new(), cache_key: None }
+    }
+}
+
+/// One row handed to a `DataSet::map` closure, as column names and values.
+///
+/// Values are read from the columns of the row's batch when asked for, and only the values the
+/// closure sets or removes are kept, so the columns it leaves alone are not copied.
+#[derive(Debug, Clone)]
+pub struct Row<'a> {
+    columns: &'a [Series],
+    index: usize,
+    /// Values set by the closure, or `None` for removed columns, by column name.
+    changes: Vec<(String, Option<AnyValue<'static>>)>,
+}
+
+impl<'a> Row<'a> {
+    /// The change made to `column`, if any: its new value or `None` if it was removed.
+    fn change(&self, column: &str) -> Option<&Option<AnyValue<'static>>> {
+        self.changes.iter().find(|(name, _)| name == column).map(|(_, value)| value)
+    }
+
+    /// The batch column named `column`.
+    fn series(&self, column: &str) -> Option<&'a Series> {
+        self.columns.iter().find(|series| series.name() == column)
+    }
+
+    /// The value of `column`, if the row has it.
+    pub fn get(&self, column: &str) -> Option<AnyValue<'_>> {
+        match self.change(column) {
+            Some(value) => value.as_ref().map(AnyValue::as_borrowed),
+            None => self.series(column)?.get(self.index).ok(),
+        }
+    }
+
+    /// The value of `column` if it is a string.
+    pub fn get_str(&self, column: &str) -> Option<&str> {
+        match self.change(column) {
+            Some(value) => value.as_ref()?.get_str(),
+            None => self.series(column)?.str().ok()?.get(self.index),
+        }
+    }
+
+    /// Set `column` to `value`, adding the column if the row does not have it.
+    pub fn set<V: Into<AnyValue<'static>>>(&mut self, column: &str, value: V) {
+        let value = Some(value.into());
+        match self.changes.iter_mut().find(|(name, _)| name == column) {
+            Some((_, old)) => *old = value,
+            None => self.changes.push((column.to_string(), value)),
+        }
+    }
+
+    /// Set `column` to the string `value`.
+    pub fn set_str(&mut self, column: &str, value: &str) {
+        self.set(column, AnyValue::StringOwned(value.into()));
+    }
+
+    /// Remove `column` from the row, returning its value.
+    ///
+    /// `DataSet::map` drops a column that every row removes and leaves it null in the rows that
+    /// remove it otherwise, however the rows are batched.
+    pub fn remove(&mut self, column: &str) -> Option<AnyValue<'static>> {
+        if let Some((_, change)) = self.changes.iter_mut().find(|(name, _)| name == column) {
+            return change.take();
+        }
+        let value = self.series(column)?.get(self.index).ok()?.into_static().ok()?;
+        self.changes.push((column.to_string(), None));
+        Some(value)
+    }
+
+    /// The names of the row's columns, in order.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        let kept = self
+            .columns
+            .iter()
+            .map(|series| series.name())
+            .filter(|name| !matches!(self.change(name), Some(None)));
+        let added = self
+            .changes
+            .iter()
+            .filter(|(name, value)| value.is_some() && self.series(name).is_none())
+            .map(|(name, _)| name.as_str());
+        kept.chain(added)
+    }
+}
+
+/// Row `index` of `df`.
+pub(crate) fn row_at(df: &DataFrame, index: usize) -> Row<'_> {
+    Row { columns: df.get_columns(), index, changes: Vec::new() }
+}
+
+/// Put the `rows` of `batch` back into a `DataFrame`; a column missing from some rows is null in
+/// those rows. Columns that no row changed are taken from `batch` as they are.
+///
+/// A column of `batch` that every row removed is kept as nulls, so that batches agree on it, and
+/// returned with the others removed everywhere.
+fn rows_frame(batch: &DataFrame, rows: &[Row]) -> Result<(DataFrame, Vec<String>), Box<dyn Error>> {
+    let mut changed: Vec<&str> = Vec::new();
+    for (name, _) in rows.iter().flat_map(|row| &row.changes) {
+        if !changed.contains(&name.as_str()) {
+            changed.push(name);
+        }
+    }
+    let added = changed.iter().copied().filter(|name| batch.get_column_index(name).is_none());
+    let names: Vec<&str> = batch.get_column_names().into_iter().chain(added).collect();
+
+    let mut columns = Vec::with_capacity(names.len());
+    let mut removed = Vec::new();
+    for name in names {
+        if !changed.contains(&name) {
+            columns.push(batch.column(name)?.clone());
+            continue;
+        }
+        let values: Vec<Option<AnyValue>> = rows.iter().map(|row| row.get(name)).collect();
+        if values.iter().all(Option::is_none) {
+            if let Ok(series) = batch.column(name) {
+                columns.push(Series::full_null(name, batch.height(), series.dtype()));
+                removed.push(name.to_string());
+            }
+            continue;
+        }
+        let values: Vec<AnyValue> = values.into_iter().map(|value| value.unwrap_or(AnyValue::Null)).collect();
+        columns.push(Series::from_any_values(name, &values, false)?);
+    }
+    Ok((DataFrame::new(columns)?, removed))
+}
+
+impl DataSet {
+    /// Call `f` on every row in parallel and collect the rows it leaves into a new dataset.
+    ///
+    /// `f` can change, add or remove values with `Row::set` and `Row::remove`; a column that some
+    /// rows lack is null in those rows, and a column that every row removes is dropped. The dtype
+    /// of a new column is inferred from its values.
+    ///
+    /// Every value `f` reads or sets goes through an `AnyValue`, which costs far more than a
+    /// vectorized Polars expression; for work over whole columns, use `map_batches` instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let flagged = dataset.map(|row| {
+    ///     let port = row.get("dst_port").and_then(|port| port.extract::<i64>()).unwrap_or_default();
+    ///     row.set("well_known_port", port < 1024);
+    ///     Ok(())
+    /// }, &MapOptions::default())?;
+    /// ```
+    pub fn map<F>(&self, f: F, options: &MapOptions) -> Result<DataSet, Box<dyn Error>>
+    where
+        F: Fn(&mut Row) -> Result<(), Box<dyn Error>> + Sync,
+    {
+        self.map_chunks(
+            "map",
+            |start, batch| {
+                if batch.height() == 0 {
+                    return Ok((batch, Vec::new()));
+                }
+                let mut rows: Vec<Row> = (0..batch.height()).map(|index| row_at(&batch, index)).collect();
+                for (index, row) in rows.iter_mut().enumerate() {
+                    f(row).map_err(|e| format!("Row {}: {}", start + index, e))?;
+                }
+                rows_frame(&batch, &rows)
+            },
+            options,
+        )
+    }
+
+    /// Call `f` on batches of `options.batch_size` rows in parallel and stack the batches it
+    /// returns into a new dataset.
+    ///
+    /// A returned batch may have other columns than its input, or another number of rows, to
+    /// filter or augment the data.
+    pub fn map_batches<F>(&self, f: F, options: &MapOptions) -> Result<DataSet, Box<dyn Error>>
+    where
+        F: Fn(DataFrame) -> Result<DataFrame, Box<dyn Error>> + Sync,
+    {
+        self.map_chunks(
+            "map_batches",
+            |start, batch| {
+                let end = start + batch.height();
+                let batch = f(batch).map_err(|e| format!("Batch of rows {}..{}: {}", start, end, e))?;
+                Ok((batch, Vec::new()))
+            },
+            options,
+        )
+    }
+
+    /// Run `f` on each batch with the index of its first row, then drop `options.remove_columns`
+    /// and the columns `f` says every batch removed; with a `cache_key`, the result is cached as
+    /// `transform`.
+    fn map_chunks<F>(&self, transform: &str, f: F, options: &MapOptions) -> Result<DataSet, Box<dyn Error>>
+    where
+        F: Fn(usize, DataFrame) -> Result<(DataFrame, Vec<String>), Box<dyn Error>> + Sync,
+    {
+        match &options.cache_key {
+            Some(key) => {
+                let params = params_key(&(key, options.batch_size, &options.remove_columns));
+                self.cached(transform, &params, || self.map_chunks_uncached(f, options))
+            }
+            None => self.map_chunks_uncached(f, options),
+        }
+    }
+
+    fn map_chunks_uncached<F>(&self, f: F, options: &MapOptions) -> Result<DataSet, Box<dyn Error>>
+    where
+        F: Fn(usize, DataFrame) -> Result<(DataFrame, Vec<String>), Box<dyn Error>> + Sync,
+    {
+        if options.batch_size == 0 {
+            return Err("batch_size must be at least 1".into());
+        }
+        for column in &options.remove_columns {
+            self.data.column(column)?;
+        }
+
+        let n = self.data.height();
+        let batch_size = options.batch_size;
+        let run = || {
+            // An empty dataset still goes through `f` once, so its schema changes are kept.
+            let starts: Vec<usize> = if n == 0 { vec![0] } else { (0..n).step_by(batch_size).collect() };
+            starts
+                .par_iter()
+                .map(|start| f(*start, self.data.slice(*start as i64, batch_size)).map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, String>>()
+        };
+        let batches = if options.num_workers == 0 {
+            run()?
+        } else {
+            rayon::ThreadPoolBuilder::new().num_threads(options.num_workers).build()?.install(run)?
+        };
+
+        let (batches, removed): (Vec<DataFrame>, Vec<Vec<String>>) = batches.into_iter().unzip();
+        let removed_everywhere =
+            removed[0].iter().filter(|column| removed.iter().all(|batch| batch.contains(column)));
+        let mut data = concat_frames(batches)?;
+        for column in options.remove_columns.iter().chain(removed_everywhere) {
+            if data.get_column_index(column).is_some() {
+                data = data.drop(column)?;
+            }
+        }
+        Ok(self.with_data(data))
+    }
+}
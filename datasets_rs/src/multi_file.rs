@@ -0,0 +1,130 @@
+//! # Multi-File Loading
+//!
+//! This module loads a directory, a glob such as `logs/2026-*/**/*.parquet`, or a list of paths
+//! into a single `DataFrame`. Files whose schemas differ slightly are reconciled: columns missing
+//! from a file are filled with nulls and columns with different dtypes are cast to a common
+//! supertype. Sources may also be `https://` URLs, fetched through the download cache.
+
+use polars::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::csv_options::CsvOptions;
+use crate::download::{self, DownloadOptions};
+use crate::load_dataset::DataSet;
+use crate::sniff;
+
+/// Name of the column holding the file each row was loaded from.
+pub const SOURCE_FILE_COLUMN: &str = "__source_file";
+
+/// Options for `DataSet::load_many`.
+#[derive(Debug, Clone, Default)]
+pub struct LoadManyOptions {
+    /// Add a `__source_file` column with the path of the file each row came from.
+    pub add_source_column: bool,
+    /// How `http://` and `https://` sources are fetched.
+    pub download: DownloadOptions,
+    /// Dialect of the CSV files.
+    pub csv: CsvOptions,
+}
+
+/// Returns `true` if `pattern` contains glob metacharacters.
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Returns `true` if the file has the extension of a format `DataSet::load_data` can read.
+fn is_supported(path: &Path) -> bool {
+    sniff::from_extension(path).is_some()
+}
+
+/// Recursively collect the supported files below `dir`, skipping hidden files and directories.
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            walk_dir(&path, files)?;
+        } else if is_supported(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expand a directory, glob pattern or plain file path into a sorted list of files.
+pub fn expand_source(source: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let path = Path::new(source);
+    let mut files = Vec::new();
+
+    if path.is_dir() {
+        walk_dir(path, &mut files)?;
+    } else if is_glob(source) {
+        for entry in glob::glob(source)? {
+            let path = entry?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("No data files found for {}", source).into());
+    }
+    Ok(files)
+}
+
+/// Concatenate `DataFrame`s whose schemas may differ slightly.
+///
+/// Missing columns are filled with nulls and mismatching dtypes are cast to their supertype.
+pub fn concat_frames(frames: Vec<DataFrame>) -> Result<DataFrame, Box<dyn Error>> {
+    let lfs: Vec<LazyFrame> = frames.into_iter().map(DataFrame::lazy).collect();
+    if lfs.is_empty() {
+        return Ok(DataFrame::default());
+    }
+    let args = UnionArgs { to_supertypes: true, ..Default::default() };
+    Ok(concat_lf_diagonal(lfs, args)?.collect()?)
+}
+
+impl DataSet {
+    /// Load every file matched by `sources` and concatenate them into one `DataFrame`.
+    ///
+    /// Each source may be a directory (walked recursively), a glob pattern, a file path or an
+    /// `https://` URL. URLs are downloaded concurrently before any file is read.
+    pub fn load_many<S: AsRef<str>>(
+        sources: &[S],
+        options: &LoadManyOptions,
+    ) -> Result<DataFrame, Box<dyn Error>> {
+        let urls: Vec<&str> = sources.iter().map(AsRef::as_ref).filter(|s| download::is_url(s)).collect();
+        let blobs = download::download_many(&urls, &options.download)?;
+        let downloaded: HashMap<&str, PathBuf> = urls.into_iter().zip(blobs).collect();
+
+        let mut frames = Vec::new();
+        for source in sources {
+            let source = source.as_ref();
+            let files = match downloaded.get(source) {
+                Some(blob) => vec![(source.to_string(), blob.clone())],
+                None => expand_source(source)?
+                    .into_iter()
+                    .map(|file| (file.to_string_lossy().to_string(), file))
+                    .collect(),
+            };
+            for (name, file) in files {
+                let mut df = Self::load_data_with(&file, &options.csv)?;
+                if options.add_source_column {
+                    df.with_column(Series::new(SOURCE_FILE_COLUMN, vec![name.as_str(); df.height()]))?;
+                }
+                log::info!("Loaded {} rows from {}", df.height(), name);
+                frames.push(df);
+            }
+        }
+        concat_frames(frames)
+    }
+}
@@ -0,0 +1,114 @@
+//! # JSON Lines (NDJSON)
+//!
+//! This module reads and writes newline-delimited JSON, one record per line. Reading is done in
+//! batches of lines from any `BufRead`, so a file never has to be held in memory as one JSON
+//! value; each batch is parsed into a `DataFrame` of its own, and `read_ndjson` stacks them as in
+//! `concat_frames`.
+
+use polars::prelude::*;
+use std::error::Error;
+use std::io::{BufRead, Cursor, Write};
+
+use crate::multi_file::concat_frames;
+
+/// Number of lines parsed into one `DataFrame` when no batch size is given.
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// An iterator over `DataFrame` batches read line by line from an NDJSON source.
+///
+/// Each batch's schema is inferred from its own lines, so a key first seen in a later batch, or
+/// a value widening a column, is kept; batches can then differ and are stacked with
+/// `concat_frames`. With `with_schema`, every batch is parsed with that schema instead.
+pub struct NdjsonBatches<R: BufRead> {
+    reader: R,
+    batch_size: usize,
+    schema: Option<SchemaRef>,
+    done: bool,
+}
+
+impl<R: BufRead> NdjsonBatches<R> {
+    /// Create a batch iterator reading `batch_size` lines at a time.
+    pub fn new(reader: R, batch_size: usize) -> Self {
+        NdjsonBatches { reader, batch_size: batch_size.max(1), schema: None, done: false }
+    }
+
+    /// Parse every batch with a fixed schema instead of inferring one per batch.
+    pub fn with_schema(mut self, schema: SchemaRef) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Read up to `batch_size` non-empty lines into a buffer.
+    fn read_lines(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buf = Vec::new();
+        let mut line = Vec::new();
+        let mut lines = 0;
+
+        while lines < self.batch_size {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.done = true;
+                break;
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            buf.extend_from_slice(&line);
+            if !line.ends_with(b"\n") {
+                buf.push(b'\n');
+            }
+            lines += 1;
+        }
+
+        Ok(buf)
+    }
+
+    fn parse_batch(&self, buf: Vec<u8>) -> Result<DataFrame, Box<dyn Error>> {
+        let mut reader = JsonReader::new(Cursor::new(buf))
+            .with_json_format(JsonFormat::JsonLines)
+            .infer_schema_len(None);
+        if let Some(schema) = &self.schema {
+            reader = reader.with_schema(schema.clone());
+        }
+        Ok(reader.finish()?)
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonBatches<R> {
+    type Item = Result<DataFrame, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let buf = match self.read_lines() {
+            Ok(buf) => buf,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        if buf.is_empty() {
+            return None;
+        }
+        Some(self.parse_batch(buf))
+    }
+}
+
+/// Read a whole NDJSON source into a single `DataFrame`, batch by batch.
+///
+/// Batches are aligned as in `concat_frames`: keys missing from a batch are null in its rows and
+/// mismatching dtypes are cast to their supertype.
+pub fn read_ndjson<R: BufRead>(reader: R, batch_size: usize) -> Result<DataFrame, Box<dyn Error>> {
+    let batches = NdjsonBatches::new(reader, batch_size).collect::<Result<Vec<_>, _>>()?;
+    let mut df = concat_frames(batches)?;
+    df.align_chunks();
+    Ok(df)
+}
+
+/// Write a `DataFrame` as NDJSON, one JSON object per row.
+pub fn write_ndjson<W: Write>(df: &DataFrame, writer: W) -> Result<(), Box<dyn Error>> {
+    let mut df = df.clone();
+    JsonWriter::new(writer).with_json_format(JsonFormat::JsonLines).finish(&mut df)?;
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! # Sharding
+//!
+//! This module splits a `DataSet` into shards for distributed and parallel processing, following
+//! Hugging Face `Dataset.shard`: a contiguous shard is a block of consecutive rows, otherwise shard
+//! `i` holds every `num_shards`-th row starting at row `i`. Shards depend only on the row order,
+//! so shuffling with a seed first gives the same shards for the same input and seed.
+//!
+//! `DataSet::save_shards` writes every shard to a file next to a `shards.json` manifest, from
+//! which each worker loads its own shard with `DataSet::load_shard`.
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::cache::params_key;
+use crate::download::file_sha256;
+use crate::load_dataset::DataSet;
+use crate::split::take_rows;
+
+pub const MANIFEST_FILE: &str = "shards.json";
+
+/// A shard file listed in the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardEntry {
+    pub index: usize,
+    pub filename: String,
+    pub num_rows: usize,
+    /// SHA-256 of the file, checked by `DataSet::load_shard`.
+    pub sha256: String,
+}
+
+/// The contents of `shards.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardManifest {
+    pub num_shards: usize,
+    pub contiguous: bool,
+    pub num_rows: usize,
+    /// Seed the rows were shuffled with before sharding, from the dataset's `info`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub shards: Vec<ShardEntry>,
+}
+
+impl ShardManifest {
+    /// Read the manifest of a directory written by `DataSet::save_shards`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn Error>> {
+        let path = dir.as_ref().join(MANIFEST_FILE);
+        let file = File::open(&path).map_err(|e| format!("Cannot open shard manifest {}: {}", path.display(), e))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+/// The rows of contiguous shard `index` out of `num_shards` over `n` rows.
+///
+/// The first `n % num_shards` shards get one row more than the others, as in Hugging Face.
+pub fn shard_range(n: usize, num_shards: usize, index: usize) -> Range<usize> {
+    let (size, extra) = (n / num_shards, n % num_shards);
+    let start = size * index + index.min(extra);
+    let end = start + size + usize::from(index < extra);
+    start..end
+}
+
+/// Check that `index` names one of `num_shards` shards.
+fn check_shard(num_shards: usize, index: usize) -> Result<(), Box<dyn Error>> {
+    if num_shards == 0 {
+        return Err("num_shards must be at least 1".into());
+    }
+    if index >= num_shards {
+        return Err(format!("Shard index {} is out of range for {} shards", index, num_shards).into());
+    }
+    Ok(())
+}
+
+impl DataSet {
+    /// Shard `index` out of `num_shards`, as a block of consecutive rows if `contiguous`, otherwise
+    /// every `num_shards`-th row starting at row `index`.
+    ///
+    /// The shard is fingerprinted from the dataset's fingerprint, so transforms on it are cached.
+    pub fn shard(&self, num_shards: usize, index: usize, contiguous: bool) -> Result<DataSet, Box<dyn Error>> {
+        check_shard(num_shards, index)?;
+        let n = self.data.height();
+        let data = if contiguous {
+            let rows = shard_range(n, num_shards, index);
+            self.data.slice(rows.start as i64, rows.len())
+        } else {
+            take_rows(&self.data, (index..n).step_by(num_shards).map(|row| row as IdxSize).collect())?
+        };
+        let mut shard = self.with_data(data);
+        shard.fingerprint = self.derive_fingerprint("shard", &params_key(&(num_shards, index, contiguous)));
+        Ok(shard)
+    }
+
+    /// Write all `num_shards` shards as `<dir>/shard-00000-of-0000N.<file_extension>` with a
+    /// `shards.json` manifest listing each file, its row count and checksum.
+    pub fn save_shards<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        num_shards: usize,
+        contiguous: bool,
+        file_extension: &str,
+    ) -> Result<ShardManifest, Box<dyn Error>> {
+        check_shard(num_shards, 0)?;
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut shards = Vec::with_capacity(num_shards);
+        for index in 0..num_shards {
+            let shard = self.shard(num_shards, index, contiguous)?;
+            let filename = format!("shard-{:05}-of-{:05}.{}", index, num_shards, file_extension);
+            let path = dir.join(&filename);
+            shard.save_data(&path, file_extension)?;
+            shards.push(ShardEntry { index, filename, num_rows: shard.data.height(), sha256: file_sha256(&path)? });
+        }
+
+        let num_rows = self.data.height();
+        let manifest = ShardManifest { num_shards, contiguous, num_rows, seed: self.info.seed, shards };
+        serde_json::to_writer_pretty(BufWriter::new(File::create(dir.join(MANIFEST_FILE))?), &manifest)?;
+        log::info!("Wrote {} shards of {} rows to {}", num_shards, manifest.num_rows, dir.display());
+        Ok(manifest)
+    }
+
+    /// Load shard `index` of a directory written by `save_shards`, checking it against the manifest.
+    pub fn load_shard<P: AsRef<Path>>(dir: P, index: usize) -> Result<DataSet, Box<dyn Error>> {
+        let dir = dir.as_ref();
+        let manifest = ShardManifest::load(dir)?;
+        check_shard(manifest.num_shards, index)?;
+        let entry = manifest
+            .shards
+            .iter()
+            .find(|entry| entry.index == index)
+            .ok_or_else(|| format!("Shard {} is missing from the manifest in {}", index, dir.display()))?;
+
+        let path = dir.join(&entry.filename);
+        let sha256 = file_sha256(&path)?;
+        if sha256 != entry.sha256 {
+            return Err(format!("Shard {} does not match its manifest checksum", path.display()).into());
+        }
+        let mut dataset = DataSet::new(DataSet::load_data(&path)?);
+        if dataset.data.height() != entry.num_rows {
+            let rows = dataset.data.height();
+            return Err(format!("Shard {} has {} rows, the manifest lists {}", path.display(), rows, entry.num_rows).into());
+        }
+        dataset.info.seed = manifest.seed;
+        Ok(dataset)
+    }
+}
@@ -0,0 +1,195 @@
+//! # Format Sniffing
+//!
+//! This module detects the format of a file from its content rather than its extension. It
+//! recognises the Parquet (`PAR1`) and Arrow IPC (`ARROW1`) magic bytes, the gzip, zstd, bzip2
+//! and xz headers, JSON arrays versus JSON Lines, and the delimiter of CSV/TSV text.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::compression::{self, Compression};
+
+/// Number of bytes inspected at the start of a file.
+const SNIFF_LEN: usize = 8192;
+
+/// Delimiters tried, in order of preference, when sniffing delimited text.
+const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Every format a file can be sniffed as.
+pub const FORMATS: [&str; 6] = ["csv", "json", "jsonl", "parquet", "arrow", "arrows"];
+
+/// The result of sniffing a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SniffedFormat {
+    /// One of `csv`, `json`, `jsonl`, `parquet`, `arrow` or `arrows` (Arrow IPC stream).
+    pub format: &'static str,
+    pub compression: Compression,
+    /// Field delimiter, only meaningful for `csv`.
+    pub separator: u8,
+}
+
+impl fmt::Display for SniffedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format)?;
+        if self.format == "csv" {
+            write!(f, " (separator {:?})", self.separator as char)?;
+        }
+        if self.compression != Compression::None {
+            write!(f, ", {}-compressed", self.compression.extension())?;
+        }
+        Ok(())
+    }
+}
+
+/// Detect the compression codec from a file header.
+pub fn sniff_compression(head: &[u8]) -> Compression {
+    if head.starts_with(&[0x1f, 0x8b]) {
+        Compression::Gzip
+    } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Compression::Zstd
+    } else if head.starts_with(b"BZh") {
+        Compression::Bzip2
+    } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Compression::Xz
+    } else {
+        Compression::None
+    }
+}
+
+/// Detect the format of uncompressed content from its first bytes.
+///
+/// Returns `None` if the content is binary and matches no known magic bytes.
+pub fn sniff_content(head: &[u8]) -> Option<SniffedFormat> {
+    let sniffed = |format| Some(SniffedFormat { format, compression: Compression::None, separator: b',' });
+
+    if head.starts_with(b"PAR1") {
+        return sniffed("parquet");
+    }
+    if head.starts_with(b"ARROW1") {
+        return sniffed("arrow");
+    }
+    if head.starts_with(&[0xff, 0xff, 0xff, 0xff]) {
+        return sniffed("arrows");
+    }
+
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.contains('\0') {
+        return None;
+    }
+    // An array of records or arrays; a CSV header can also start with `[`, as in `[timestamp],src`.
+    if text.strip_prefix('[').is_some_and(|rest| rest.trim_start().starts_with(['{', '[', ']'])) {
+        return sniffed("json");
+    }
+    if text.starts_with('{') {
+        // One complete object per line is JSON Lines; an object spread over lines is plain JSON.
+        let first_line = text.lines().next().unwrap_or("");
+        return match serde_json::from_str::<serde_json::Value>(first_line) {
+            Ok(_) => sniffed("jsonl"),
+            Err(_) => sniffed("json"),
+        };
+    }
+
+    Some(SniffedFormat { format: "csv", compression: Compression::None, separator: sniff_delimiter(text) })
+}
+
+/// Pick the delimiter that splits the first lines into the same, largest number of fields.
+pub fn sniff_delimiter(text: &str) -> u8 {
+    // The last line may have been cut off at `SNIFF_LEN`.
+    let mut lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).take(21).collect();
+    if lines.len() > 1 {
+        lines.pop();
+    }
+
+    let mut best = (b',', 0);
+    for delimiter in DELIMITERS {
+        let counts: Vec<usize> = lines
+            .iter()
+            .map(|line| line.bytes().filter(|b| *b == delimiter).count())
+            .collect();
+        let Some(&first) = counts.first() else { continue };
+        if first > best.1 && counts.iter().all(|c| *c == first) {
+            best = (delimiter, first);
+        }
+    }
+    best.0
+}
+
+/// Read up to `SNIFF_LEN` bytes from a reader.
+fn read_head<R: Read>(reader: R) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    reader.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Sniff the compression and format of a file, looking inside compressed streams.
+pub fn sniff_path<P: AsRef<Path>>(file_path: P) -> Result<Option<SniffedFormat>, Box<dyn Error>> {
+    let path = file_path.as_ref();
+    let head = read_head(File::open(path)?)?;
+
+    let compression = sniff_compression(&head);
+    if compression == Compression::None {
+        return Ok(sniff_content(&head));
+    }
+
+    let inner = read_head(compression::open_decompressed(path, compression)?)?;
+    Ok(sniff_content(&inner).map(|sniffed| SniffedFormat { compression, ..sniffed }))
+}
+
+/// Sniff a file known to be in `format`, one of `FORMATS`, whatever its content looks like: only
+/// its compression and, for CSV, its delimiter are sniffed.
+pub fn sniff_path_as<P: AsRef<Path>>(file_path: P, format: &str) -> Result<SniffedFormat, Box<dyn Error>> {
+    let path = file_path.as_ref();
+    let format = FORMATS
+        .into_iter()
+        .find(|known| *known == format)
+        .ok_or_else(|| format!("Unsupported file format: {}", format))?;
+    let mut head = read_head(File::open(path)?)?;
+    let compression = sniff_compression(&head);
+    if compression != Compression::None {
+        head = read_head(compression::open_decompressed(path, compression)?)?;
+    }
+    let separator = if format == "csv" { sniff_delimiter(&String::from_utf8_lossy(&head)) } else { b',' };
+    Ok(SniffedFormat { format, compression, separator })
+}
+
+/// The format implied by a path's (possibly compound) extension, if it is a known one.
+pub fn from_extension(path: &Path) -> Option<SniffedFormat> {
+    let (ext, compression) = compression::path_format(path);
+    let (format, separator) = match ext.as_str() {
+        "csv" => ("csv", b','),
+        "tsv" => ("csv", b'\t'),
+        "json" => ("json", b','),
+        "jsonl" | "ndjson" => ("jsonl", b','),
+        "parquet" => ("parquet", b','),
+        "arrow" | "feather" | "ipc" => ("arrow", b','),
+        "arrows" => ("arrows", b','),
+        _ => return None,
+    };
+    Some(SniffedFormat { format, compression, separator })
+}
+
+/// Detect the format of a file, trusting its content over its extension.
+///
+/// The sniffed format is logged, with a warning when it disagrees with the extension. The
+/// extension is only used when the content cannot be recognised.
+pub fn detect_format<P: AsRef<Path>>(file_path: P) -> Result<SniffedFormat, Box<dyn Error>> {
+    let path = file_path.as_ref();
+    let by_extension = from_extension(path);
+
+    match sniff_path(path)? {
+        Some(sniffed) => {
+            log::info!("Sniffed {} as {}", path.display(), sniffed);
+            if let Some(expected) = by_extension {
+                if expected.format != sniffed.format || expected.compression != sniffed.compression {
+                    log::warn!("{} is named like {} but contains {}", path.display(), expected, sniffed);
+                }
+            }
+            Ok(sniffed)
+        }
+        None => by_extension.ok_or_else(|| format!("Unrecognised file format: {}", path.display()).into()),
+    }
+}
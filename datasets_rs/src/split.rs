@@ -0,0 +1,464 @@
+//! # Shuffling and Splitting
+//!
+//! This module shuffles, samples and splits a `DataSet`. Every randomized operation takes an
+//! explicit seed, and the seed and split parameters are recorded in the resulting dataset's
+//! `info`, so a split can be regenerated exactly from its metadata.
+//!
+//! A stratified split keeps the proportion of every target class the same in each split, so rare
+//! classes, such as uncommon attack types in security data, are represented in all of them.
+//! A group split keeps all rows sharing a value, such as a `source_ip` or session, in the same
+//! split, so the same host cannot appear in both training and evaluation.
+//!
+//! `DataSet::split` generalizes these to any number of named splits, such as `train`,
+//! `validation` and `test`, sized by ratios or row counts.
+
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::cache::params_key;
+use crate::dataset_dict::DatasetDict;
+use crate::load_dataset::DataSet;
+
+/// How a split was produced, stored in `DatasetInfo::split` so it can be regenerated.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SplitInfo {
+    /// The `DataSet` method that produced the split, e.g. `train_test_split`.
+    pub method: String,
+    /// Share of the rows in the `test` split.
+    pub test_ratio: f32,
+    /// The requested size of every split, for splits made by `DataSet::split`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sizes: Vec<(String, SplitSize)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stratify_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+    /// Column whose timestamps ordered the rows of a chronological split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_column: Option<String>,
+    /// Rows timestamped at or after `cutoff` went to the test split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cutoff: Option<String>,
+    /// Seed of the shuffle applied before splitting; `None` if rows kept their order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// The size of one named split: a share of the rows or a number of rows.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitSize {
+    Ratio(f32),
+    Count(usize),
+}
+
+/// Options for `DataSet::split`.
+#[derive(Debug, Clone, Default)]
+pub struct SplitOptions {
+    /// Shuffle the rows, or the groups, with this seed before splitting.
+    pub seed: Option<u64>,
+    /// Keep the class proportions of this column in every split.
+    pub stratify_by: Option<String>,
+    /// Keep all rows sharing a value of this column in the same split.
+    pub group_by: Option<String>,
+}
+
+/// Parse split sizes such as `train=0.8,validation=0.1,test=0.1` or `train=0.9,test=1000`.
+///
+/// A value with a decimal point is a ratio; a whole number is a row count.
+pub fn parse_split_sizes(spec: &str) -> Result<Vec<(String, SplitSize)>, Box<dyn Error>> {
+    spec.split(',')
+        .map(|part| {
+            let (name, size) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected name=size in split sizes, got {}", part))?;
+            let size = if size.contains('.') {
+                SplitSize::Ratio(size.trim().parse()?)
+            } else {
+                SplitSize::Count(size.trim().parse()?)
+            };
+            Ok((name.trim().to_string(), size))
+        })
+        .collect()
+}
+
+/// The end of each split within `n` rows, after the rows of the splits before it.
+///
+/// Sizes are resolved to fractional row counts and accumulated before rounding, so ratios that
+/// sum to 1 always cover every row. Rows past the last split are left out.
+fn split_boundaries(sizes: &[(String, SplitSize)], n: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut total = 0.0;
+    let mut boundaries = Vec::with_capacity(sizes.len());
+    for (name, size) in sizes {
+        total += match *size {
+            SplitSize::Ratio(ratio) if (0.0..=1.0).contains(&ratio) => ratio as f64 * n as f64,
+            SplitSize::Ratio(ratio) => {
+                return Err(format!("Ratio of split {} must be between 0 and 1, got {}", name, ratio).into())
+            }
+            SplitSize::Count(count) => count as f64,
+        };
+        boundaries.push(total.round() as usize);
+    }
+    match boundaries.last() {
+        Some(end) if *end > n => Err(format!("Split sizes add up to {} rows, but the dataset has {}", end, n).into()),
+        Some(_) => Ok(boundaries),
+        None => Err("No splits requested".into()),
+    }
+}
+
+/// Cut `rows` into consecutive parts ending at `boundaries`.
+fn cut(rows: &[IdxSize], boundaries: &[usize]) -> Vec<Vec<IdxSize>> {
+    let mut start = 0;
+    boundaries
+        .iter()
+        .map(|end| {
+            let part = rows[start..*end].to_vec();
+            start = *end;
+            part
+        })
+        .collect()
+}
+
+/// A random permutation of `0..n`, the same for the same seed.
+pub fn permutation(n: usize, seed: u64) -> Vec<IdxSize> {
+    let mut rows: Vec<IdxSize> = (0..n as IdxSize).collect();
+    rows.shuffle(&mut StdRng::seed_from_u64(seed));
+    rows
+}
+
+/// Row indices grouped by label, as returned by `class_indices`.
+pub type Classes = Vec<(Option<String>, Vec<IdxSize>)>;
+
+/// Group row indices by the value of `labels`, in order of first appearance.
+///
+/// Labels of any dtype are compared by their string form; nulls form a class of their own.
+pub fn class_indices(labels: &Series) -> Result<Classes, Box<dyn Error>> {
+    let labels = labels.cast(&DataType::String)?;
+    let mut classes: Classes = Vec::new();
+    let mut positions: HashMap<Option<&str>, usize> = HashMap::new();
+    for (row, label) in labels.str()?.into_iter().enumerate() {
+        let position = *positions.entry(label).or_insert_with(|| {
+            classes.push((label.map(str::to_string), Vec::new()));
+            classes.len() - 1
+        });
+        classes[position].1.push(row as IdxSize);
+    }
+    Ok(classes)
+}
+
+/// Take the given rows of `df`, in the given order.
+pub fn take_rows(df: &DataFrame, rows: Vec<IdxSize>) -> Result<DataFrame, Box<dyn Error>> {
+    Ok(df.take(&IdxCa::from_vec("", rows))?)
+}
+
+/// Assign row indices to train and test so each class keeps its proportion in both.
+///
+/// The last `test_ratio` of each class's rows go to the test split, after shuffling them with
+/// `seed` if one is given. Fails if a class has too few rows to appear in both splits.
+pub fn stratified_indices(
+    labels: &Series,
+    test_ratio: f32,
+    seed: Option<u64>,
+) -> Result<(Vec<IdxSize>, Vec<IdxSize>), Box<dyn Error>> {
+    if !(test_ratio > 0.0 && test_ratio < 1.0) {
+        return Err(format!("test_ratio must be strictly between 0 and 1, got {}", test_ratio).into());
+    }
+
+    let mut rng = seed.map(StdRng::seed_from_u64);
+    let mut train = Vec::with_capacity(labels.len());
+    let mut test = Vec::new();
+    for (label, mut rows) in class_indices(labels)? {
+        let n = rows.len();
+        let test_size = (n as f32 * test_ratio).round() as usize;
+        if test_size == 0 || test_size == n {
+            return Err(format!(
+                "Class {} of column {} has {} rows, too few to appear in both train and test at test_ratio {}",
+                label.as_deref().unwrap_or("null"),
+                labels.name(),
+                n,
+                test_ratio
+            )
+            .into());
+        }
+        if let Some(rng) = rng.as_mut() {
+            rows.shuffle(rng);
+        }
+        log::info!("Class {}: {} train, {} test", label.as_deref().unwrap_or("null"), n - test_size, test_size);
+        train.extend_from_slice(&rows[..n - test_size]);
+        test.extend_from_slice(&rows[n - test_size..]);
+    }
+
+    // Keep the rows of each split in their original order.
+    train.sort_unstable();
+    test.sort_unstable();
+    Ok((train, test))
+}
+
+/// Assign whole groups of rows to train and test so the test set holds about `test_ratio` of the rows.
+///
+/// Groups are visited in order of first appearance, or shuffled with `seed` if one is given, and
+/// each goes to the test set while that brings its row count closer to the target. Fails if either
+/// split would end up empty.
+pub fn group_indices(
+    groups: &Series,
+    test_ratio: f32,
+    seed: Option<u64>,
+) -> Result<(Vec<IdxSize>, Vec<IdxSize>), Box<dyn Error>> {
+    if !(test_ratio > 0.0 && test_ratio < 1.0) {
+        return Err(format!("test_ratio must be strictly between 0 and 1, got {}", test_ratio).into());
+    }
+
+    let mut classes = class_indices(groups)?;
+    if let Some(seed) = seed {
+        classes.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    let target = groups.len() as f32 * test_ratio;
+    let mut train = Vec::with_capacity(groups.len());
+    let mut test = Vec::new();
+    for (_, rows) in classes {
+        let with_group = (test.len() + rows.len()) as f32;
+        if (with_group - target).abs() < (test.len() as f32 - target).abs() {
+            test.extend(rows);
+        } else {
+            train.extend(rows);
+        }
+    }
+    if train.is_empty() || test.is_empty() {
+        return Err(format!(
+            "Cannot split the groups of column {} into non-empty train and test sets at test_ratio {}",
+            groups.name(),
+            test_ratio
+        )
+        .into());
+    }
+    log::info!("Group split: {} train rows, {} test rows", train.len(), test.len());
+
+    train.sort_unstable();
+    test.sort_unstable();
+    Ok((train, test))
+}
+
+/// Assign the rows of each class to the splits in the proportions of `sizes`.
+fn stratified_parts(
+    labels: &Series,
+    sizes: &[(String, SplitSize)],
+    seed: Option<u64>,
+) -> Result<Vec<Vec<IdxSize>>, Box<dyn Error>> {
+    // Counts are turned into shares of the whole dataset, which every class then follows.
+    let n = labels.len();
+    let sizes: Vec<(String, SplitSize)> = sizes
+        .iter()
+        .map(|(name, size)| match *size {
+            SplitSize::Count(count) => (name.clone(), SplitSize::Ratio(count as f32 / n.max(1) as f32)),
+            ratio => (name.clone(), ratio),
+        })
+        .collect();
+    split_boundaries(&sizes, n)?;
+
+    let mut rng = seed.map(StdRng::seed_from_u64);
+    let mut parts = vec![Vec::new(); sizes.len()];
+    for (label, mut rows) in class_indices(labels)? {
+        if let Some(rng) = rng.as_mut() {
+            rows.shuffle(rng);
+        }
+        let boundaries = split_boundaries(&sizes, rows.len())?;
+        for ((part, rows), (name, size)) in parts.iter_mut().zip(cut(&rows, &boundaries)).zip(&sizes) {
+            if rows.is_empty() && *size != SplitSize::Ratio(0.0) {
+                return Err(format!(
+                    "Class {} of column {} has too few rows to appear in split {}",
+                    label.as_deref().unwrap_or("null"),
+                    labels.name(),
+                    name
+                )
+                .into());
+            }
+            part.extend(rows);
+        }
+    }
+    Ok(parts)
+}
+
+/// Assign whole groups to the splits, each to the split furthest below its target row count.
+fn group_parts(
+    groups: &Series,
+    sizes: &[(String, SplitSize)],
+    seed: Option<u64>,
+) -> Result<Vec<Vec<IdxSize>>, Box<dyn Error>> {
+    let boundaries = split_boundaries(sizes, groups.len())?;
+    let mut targets: Vec<usize> =
+        boundaries.iter().scan(0, |start, end| Some(end - std::mem::replace(start, *end))).collect();
+    // Rows past the last split fill a last part that is left out.
+    targets.push(groups.len() - boundaries.last().copied().unwrap_or(0));
+
+    let mut classes = class_indices(groups)?;
+    if let Some(seed) = seed {
+        classes.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    let mut parts = vec![Vec::new(); targets.len()];
+    for (_, rows) in classes {
+        let deficit = |split: usize| targets[split] as i64 - parts[split].len() as i64;
+        let split = (0..parts.len()).max_by_key(|split| (deficit(*split), std::cmp::Reverse(*split))).unwrap_or(0);
+        parts[split].extend(rows);
+    }
+    parts.pop();
+    for ((name, _), (part, target)) in sizes.iter().zip(parts.iter().zip(&targets)) {
+        if part.is_empty() && *target > 0 {
+            return Err(format!("The groups of column {} leave split {} empty", groups.name(), name).into());
+        }
+    }
+    Ok(parts)
+}
+
+impl DataSet {
+    /// Shuffle the rows; the same seed always gives the same order.
+    pub fn shuffle(&self, seed: u64) -> Result<DataSet, Box<dyn Error>> {
+        self.cached("shuffle", &params_key(&seed), || {
+            let mut shuffled = self.with_data(take_rows(&self.data, permutation(self.data.height(), seed))?);
+            shuffled.info.seed = Some(seed);
+            Ok(shuffled)
+        })
+    }
+
+    /// Draw `n` rows at random without replacement; the same seed always gives the same rows.
+    pub fn sample(&self, n: usize, seed: u64) -> Result<DataSet, Box<dyn Error>> {
+        let height = self.data.height();
+        if n > height {
+            return Err(format!("Cannot sample {} rows from a dataset of {} rows", n, height).into());
+        }
+        self.cached("sample", &params_key(&(n, seed)), || {
+            let mut rows = permutation(height, seed);
+            rows.truncate(n);
+            let mut sampled = self.with_data(take_rows(&self.data, rows)?);
+            sampled.info.seed = Some(seed);
+            Ok(sampled)
+        })
+    }
+
+    /// Name the `train` and `test` parts and record how they were made.
+    pub(crate) fn train_test_dict(&self, train: DataFrame, test: DataFrame, split: SplitInfo) -> DatasetDict {
+        let mut dict = DatasetDict::new();
+        for (name, data) in [("train", train), ("test", test)] {
+            let mut dataset = self.with_data(data);
+            dataset.info.split = Some(split.clone());
+            dict.insert(name, dataset);
+        }
+        dict
+    }
+
+    /// Split the dataset into a `train` and a `test` split.
+    ///
+    /// The last `test_ratio` of the rows form the test set, after shuffling with `seed` if one is
+    /// given; with `None` the rows keep their order, like `LazyDataSet::train_test_split`.
+    pub fn train_test_split(&self, test_ratio: f32, seed: Option<u64>) -> Result<DatasetDict, Box<dyn Error>> {
+        if !(0.0..=1.0).contains(&test_ratio) {
+            return Err(format!("test_ratio must be between 0 and 1, got {}", test_ratio).into());
+        }
+        let data = match seed {
+            Some(seed) => take_rows(&self.data, permutation(self.data.height(), seed))?,
+            None => self.data.clone(),
+        };
+        let n = data.height();
+        let test_size = (n as f32 * test_ratio).round() as usize;
+        let train_size = n - test_size;
+
+        let split = SplitInfo {
+            method: "train_test_split".to_string(),
+            test_ratio,
+            seed,
+            ..Default::default()
+        };
+        Ok(self.train_test_dict(data.slice(0, train_size), data.slice(train_size as i64, test_size), split))
+    }
+
+    /// Split into `train` and `test` keeping the class proportions of `target_column` in both.
+    ///
+    /// Which rows of a class go to the test set is drawn with `seed`; with `None` it is the last
+    /// ones. Either way the rows of each split keep their original order.
+    pub fn stratified_train_test_split(
+        &self,
+        target_column: &str,
+        test_ratio: f32,
+        seed: Option<u64>,
+    ) -> Result<DatasetDict, Box<dyn Error>> {
+        let (train, test) = stratified_indices(self.data.column(target_column)?, test_ratio, seed)?;
+
+        let split = SplitInfo {
+            method: "stratified_train_test_split".to_string(),
+            test_ratio,
+            stratify_by: Some(target_column.to_string()),
+            seed,
+            ..Default::default()
+        };
+        Ok(self.train_test_dict(take_rows(&self.data, train)?, take_rows(&self.data, test)?, split))
+    }
+
+    /// Split into `train` and `test` with every value of `group_column` in exactly one of them.
+    ///
+    /// The test set approximates `test_ratio` of the rows, not of the groups. Which groups go to
+    /// the test set is drawn with `seed`; either way the rows of each split keep their order.
+    pub fn train_test_split_by_group(
+        &self,
+        group_column: &str,
+        test_ratio: f32,
+        seed: Option<u64>,
+    ) -> Result<DatasetDict, Box<dyn Error>> {
+        let (train, test) = group_indices(self.data.column(group_column)?, test_ratio, seed)?;
+
+        let split = SplitInfo {
+            method: "train_test_split_by_group".to_string(),
+            test_ratio,
+            group_by: Some(group_column.to_string()),
+            seed,
+            ..Default::default()
+        };
+        Ok(self.train_test_dict(take_rows(&self.data, train)?, take_rows(&self.data, test)?, split))
+    }
+
+    /// Split into any number of named splits, sized by ratios or row counts, in the given order.
+    ///
+    /// Without options the rows are cut in order; `options` can shuffle them with a seed, keep
+    /// class proportions in every split or keep groups whole, which then approximates the sizes.
+    /// Rows not covered by the sizes are left out.
+    pub fn split(&self, sizes: &[(String, SplitSize)], options: &SplitOptions) -> Result<DatasetDict, Box<dyn Error>> {
+        let n = self.data.height();
+        let parts = match (&options.stratify_by, &options.group_by) {
+            (Some(_), Some(_)) => return Err("A split can be stratified or grouped, not both".into()),
+            (Some(column), None) => stratified_parts(self.data.column(column)?, sizes, options.seed)?,
+            (None, Some(column)) => group_parts(self.data.column(column)?, sizes, options.seed)?,
+            (None, None) => {
+                let rows: Vec<IdxSize> = match options.seed {
+                    Some(seed) => permutation(n, seed),
+                    None => (0..n as IdxSize).collect(),
+                };
+                cut(&rows, &split_boundaries(sizes, n)?)
+            }
+        };
+
+        let test_rows = sizes.iter().zip(&parts).find(|((name, _), _)| name == "test").map_or(0, |(_, rows)| rows.len());
+        let split = SplitInfo {
+            method: "split".to_string(),
+            test_ratio: test_rows as f32 / n.max(1) as f32,
+            sizes: sizes.to_vec(),
+            stratify_by: options.stratify_by.clone(),
+            group_by: options.group_by.clone(),
+            seed: options.seed,
+            ..Default::default()
+        };
+        let mut dict = DatasetDict::new();
+        for ((name, _), mut rows) in sizes.iter().zip(parts) {
+            // Stratified and grouped splits keep the original row order, as in the two-way splits.
+            if options.stratify_by.is_some() || options.group_by.is_some() {
+                rows.sort_unstable();
+            }
+            let mut dataset = self.with_data(take_rows(&self.data, rows)?);
+            dataset.info.split = Some(split.clone());
+            dict.insert(name.as_str(), dataset);
+        }
+        Ok(dict)
+    }
+}
@@ -0,0 +1,253 @@
+//! # Split Expressions
+//!
+//! This module parses Hugging Face split expressions and applies them to a `DatasetDict`, so a
+//! subset of the loaded splits can be picked without writing code:
+//!
+//! - `train` takes a whole split;
+//! - `train[10:20]` and `train[-100:]` take rows by absolute index, negative from the end;
+//! - `train[:10%]` and `test[50%:]` take rows by percentage;
+//! - `train[:33%](pct1_dropremainder)` picks how percentage boundaries are rounded: `closest`
+//!   (the default) rounds to the nearest row, `pct1_dropremainder` makes every 1% the same
+//!   number of rows, dropping the remainder;
+//! - `train[:10%]+test[50%:]` concatenates parts in order.
+
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::dataset_dict::DatasetDict;
+use crate::load_dataset::DataSet;
+use crate::multi_file::concat_frames;
+
+/// How percentage boundaries are turned into row indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Round `percent * rows / 100` to the nearest row, ties to even as in Hugging Face.
+    #[default]
+    Closest,
+    /// Use `percent * (rows / 100)` rows, so every 1% has the same size; needs at least 100 rows.
+    Pct1DropRemainder,
+}
+
+/// One end of a slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Absolute(i64),
+    Percent(i64),
+}
+
+/// A slice of one split, such as `train[:10%]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitSlice {
+    pub split: String,
+    pub from: Option<Bound>,
+    pub to: Option<Bound>,
+    pub rounding: Rounding,
+}
+
+/// A parsed split expression: slices to concatenate in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitExpr {
+    pub slices: Vec<SplitSlice>,
+}
+
+/// A cursor over the expression being parsed.
+struct Parser<'a> {
+    expr: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Fail at the current position, so the message points at the offending character.
+    fn error<T>(&self, message: &str) -> Result<T, Box<dyn Error>> {
+        Err(format!("Invalid split expression \"{}\" at position {}: {}", self.expr, self.position, message).into())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.expr[self.position..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    /// The longest run of characters matching `accept`.
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while let Some(c) = self.peek().filter(|c| accept(*c)) {
+            self.position += c.len_utf8();
+        }
+        &self.expr[start..self.position]
+    }
+
+    fn split_name(&mut self) -> Result<String, Box<dyn Error>> {
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if name.is_empty() {
+            return self.error("expected a split name");
+        }
+        Ok(name.to_string())
+    }
+
+    /// A bound such as `10`, `-5` or `33%`, or `None` if the slice leaves it out.
+    fn bound(&mut self) -> Result<Option<Bound>, Box<dyn Error>> {
+        let start = self.position;
+        let negative = self.eat('-');
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            if negative {
+                return self.error("expected digits after '-'");
+            }
+            return Ok(None);
+        }
+        let value: i64 = match digits.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                self.position = start;
+                return self.error(&format!("{} is too large", digits));
+            }
+        };
+        let value = if negative { -value } else { value };
+        if self.peek() == Some('.') {
+            return self.error("percentages and indices must be whole numbers");
+        }
+        if !self.eat('%') {
+            return Ok(Some(Bound::Absolute(value)));
+        }
+        if !(-100..=100).contains(&value) {
+            self.position = start;
+            return self.error(&format!("percentage {}% is outside -100%..100%", value));
+        }
+        Ok(Some(Bound::Percent(value)))
+    }
+
+    fn slice(&mut self) -> Result<SplitSlice, Box<dyn Error>> {
+        self.skip_whitespace();
+        let split = self.split_name()?;
+        let (mut from, mut to) = (None, None);
+        if self.eat('[') {
+            from = self.bound()?;
+            if !self.eat(':') {
+                return self.error("expected ':' in slice");
+            }
+            to = self.bound()?;
+            if !self.eat(']') {
+                return self.error("expected ']' to close the slice");
+            }
+            let mixed = matches!(
+                (from, to),
+                (Some(Bound::Absolute(_)), Some(Bound::Percent(_))) | (Some(Bound::Percent(_)), Some(Bound::Absolute(_)))
+            );
+            if mixed {
+                return self.error("a slice cannot mix absolute and percentage bounds");
+            }
+        }
+
+        let mut rounding = Rounding::default();
+        if self.eat('(') {
+            let start = self.position;
+            rounding = match self.take_while(|c| c.is_ascii_alphanumeric() || c == '_') {
+                "closest" => Rounding::Closest,
+                "pct1_dropremainder" => Rounding::Pct1DropRemainder,
+                other => {
+                    self.position = start;
+                    let message = format!("unknown rounding \"{}\"; expected closest or pct1_dropremainder", other);
+                    return self.error(&message);
+                }
+            };
+            if !self.eat(')') {
+                return self.error("expected ')' after the rounding");
+            }
+            if !matches!(from, Some(Bound::Percent(_))) && !matches!(to, Some(Bound::Percent(_))) {
+                return self.error("rounding only applies to percentage slices");
+            }
+        }
+        self.skip_whitespace();
+        Ok(SplitSlice { split, from, to, rounding })
+    }
+}
+
+impl SplitExpr {
+    /// Parse an expression such as `train[:10%]+test[50%:]`.
+    pub fn parse(expr: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parser = Parser { expr, position: 0 };
+        let mut slices = vec![parser.slice()?];
+        while parser.eat('+') {
+            slices.push(parser.slice()?);
+        }
+        if parser.position < expr.len() {
+            return parser.error("expected '+' or the end of the expression");
+        }
+        Ok(SplitExpr { slices })
+    }
+
+    /// Take the slices from `splits` and concatenate them into one `DataSet`.
+    ///
+    /// The result keeps the `uuid`, `timestamp` and `info` of the first slice's split. Splits
+    /// loaded from different files may disagree on dtypes; these are cast as in `concat_frames`.
+    pub fn apply(&self, splits: &DatasetDict) -> Result<DataSet, Box<dyn Error>> {
+        let mut first: Option<&DataSet> = None;
+        let mut parts = Vec::with_capacity(self.slices.len());
+        for slice in &self.slices {
+            let dataset = splits.get(&slice.split).ok_or_else(|| {
+                format!("No split named {}; available splits: {}", slice.split, splits.split_names().join(", "))
+            })?;
+            let (start, end) = slice.rows(dataset.data.height())?;
+            parts.push(dataset.data.slice(start as i64, end - start));
+            first.get_or_insert(dataset);
+        }
+        let first = first.ok_or("Empty split expression")?;
+        Ok(first.with_data(concat_frames(parts)?))
+    }
+}
+
+impl SplitSlice {
+    /// The `start..end` rows this slice takes out of a split of `n` rows.
+    pub fn rows(&self, n: usize) -> Result<(usize, usize), Box<dyn Error>> {
+        if self.rounding == Rounding::Pct1DropRemainder && n < 100 {
+            return Err(format!(
+                "Split {} has {} rows; pct1_dropremainder rounding needs at least 100",
+                self.split, n
+            )
+            .into());
+        }
+        let resolve = |bound: Bound| -> usize {
+            match bound {
+                Bound::Absolute(index) if index < 0 => n.saturating_sub(index.unsigned_abs() as usize),
+                Bound::Absolute(index) => (index as usize).min(n),
+                Bound::Percent(percent) => {
+                    let percent = if percent < 0 { percent + 100 } else { percent };
+                    match self.rounding {
+                        Rounding::Closest => (percent as f64 * n as f64 / 100.0).round_ties_even() as usize,
+                        Rounding::Pct1DropRemainder => percent as usize * (n / 100),
+                    }
+                }
+            }
+        };
+        let start = self.from.map_or(0, resolve);
+        let end = self.to.map_or(n, resolve);
+        Ok((start, end.max(start)))
+    }
+}
+
+impl FromStr for SplitExpr {
+    type Err = Box<dyn Error>;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        SplitExpr::parse(expr)
+    }
+}
+
+impl DatasetDict {
+    /// Select the rows named by a split expression such as `train[:10%]+test[50%:]`.
+    pub fn select_split(&self, expr: &str) -> Result<DataSet, Box<dyn Error>> {
+        SplitExpr::parse(expr)?.apply(self)
+    }
+}
@@ -0,0 +1,195 @@
+//! # Time-Based Splits
+//!
+//! This module splits a `DataSet` chronologically, so detection models are only evaluated on
+//! traffic recorded after the traffic they were trained on. It offers a split at a cutoff
+//! timestamp, a split holding out the last part of the time span, and rolling-origin folds for
+//! backtesting. Timestamps stored as strings, like `SecurityRecord::timestamp`, are first parsed
+//! into a `Datetime` column.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use std::error::Error;
+
+use crate::cross_validation::Folds;
+use crate::dataset_dict::DatasetDict;
+use crate::load_dataset::DataSet;
+use crate::split::{take_rows, SplitInfo};
+
+/// Options for `DataSet::rolling_origin`.
+#[derive(Debug, Clone)]
+pub struct RollingOriginOptions {
+    /// Number of `(train, validation)` folds.
+    pub n_splits: usize,
+    /// Rows in each validation window; by default the rows are divided into `n_splits + 1` parts.
+    pub test_size: Option<usize>,
+    /// Keep only the latest rows of each training window, making it a rolling rather than an
+    /// expanding window.
+    pub max_train_size: Option<usize>,
+    /// Rows left out between the end of each training window and its validation window.
+    pub gap: usize,
+}
+
+impl Default for RollingOriginOptions {
+    fn default() -> Self {
+        RollingOriginOptions { n_splits: 5, test_size: None, max_train_size: None, gap: 0 }
+    }
+}
+
+/// Parse a timestamp such as a split cutoff: RFC 3339, `%Y-%m-%d %H:%M:%S`, `%Y-%m-%dT%H:%M:%S`
+/// or a bare `%Y-%m-%d` date, which means midnight.
+pub fn parse_timestamp(value: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.naive_utc());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(datetime);
+        }
+    }
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default()),
+        Err(_) => Err(format!("Cannot parse timestamp {}", value).into()),
+    }
+}
+
+/// Row indices sorted by time; rows with equal timestamps keep their order.
+fn chronological_order(millis: &[i64]) -> Vec<IdxSize> {
+    let mut order: Vec<IdxSize> = (0..millis.len() as IdxSize).collect();
+    order.sort_by_key(|row| millis[*row as usize]);
+    order
+}
+
+impl DataSet {
+    /// Replace the timestamps in `column` by a `Datetime` column.
+    ///
+    /// Strings are parsed with `format`, or a format inferred from the data if `None`, and any
+    /// value that does not parse is an error. Dates become midnight; datetimes are kept as is.
+    pub fn parse_datetime(&self, column: &str, format: Option<&str>) -> Result<DataSet, Box<dyn Error>> {
+        let series = self.data.column(column)?;
+        let parsed = match series.dtype() {
+            DataType::Datetime(_, _) => return Ok(self.clone()),
+            DataType::Date => series.cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?,
+            DataType::String => {
+                let options = StrptimeOptions { format: format.map(str::to_string), ..Default::default() };
+                let expr = col(column).str().to_datetime(Some(TimeUnit::Milliseconds), None, options, lit("raise"));
+                self.data.select([column])?.lazy().select([expr]).collect()?.column(column)?.clone()
+            }
+            other => {
+                return Err(format!("Column {} has dtype {}; expected timestamps as strings or dates", column, other).into())
+            }
+        };
+        let mut data = self.data.clone();
+        data.replace(column, parsed)?;
+        Ok(self.with_data(data))
+    }
+
+    /// The dataset with `time_column` parsed, and its timestamps in milliseconds since the epoch.
+    fn timestamps(&self, time_column: &str) -> Result<(DataSet, Vec<i64>), Box<dyn Error>> {
+        let parsed = self.parse_datetime(time_column, None)?;
+        let series = parsed.data.column(time_column)?;
+        if series.null_count() > 0 {
+            return Err(format!("Column {} has {} missing timestamps", time_column, series.null_count()).into());
+        }
+        let time_zone = match series.dtype() {
+            DataType::Datetime(_, time_zone) => time_zone.clone(),
+            _ => None,
+        };
+        let millis = series.cast(&DataType::Datetime(TimeUnit::Milliseconds, time_zone))?.cast(&DataType::Int64)?;
+        let millis = millis.i64()?.into_no_null_iter().collect();
+        Ok((parsed, millis))
+    }
+
+    /// Put the rows before `cutoff` in `train` and the others in `test`, each in time order.
+    fn split_at_millis(&self, millis: &[i64], cutoff: i64, split: SplitInfo) -> Result<DatasetDict, Box<dyn Error>> {
+        let order = chronological_order(millis);
+        let boundary = order.partition_point(|row| millis[*row as usize] < cutoff);
+        if boundary == 0 || boundary == order.len() {
+            return Err(format!(
+                "Cutoff {} leaves the {} split empty",
+                split.cutoff.as_deref().unwrap_or_default(),
+                if boundary == 0 { "train" } else { "test" }
+            )
+            .into());
+        }
+        log::info!("Time split at {:?}: {} train, {} test", split.cutoff, boundary, order.len() - boundary);
+
+        let (train, test) = order.split_at(boundary);
+        Ok(self.train_test_dict(take_rows(&self.data, train.to_vec())?, take_rows(&self.data, test.to_vec())?, split))
+    }
+
+    /// Split into `train`, with the rows timestamped before `cutoff`, and `test`, with the rest.
+    ///
+    /// `time_column` is parsed into a `Datetime` column first, and both splits are sorted by it.
+    /// Timestamps with a time zone are compared to `cutoff` in UTC.
+    pub fn train_test_split_at(&self, time_column: &str, cutoff: NaiveDateTime) -> Result<DatasetDict, Box<dyn Error>> {
+        let (parsed, millis) = self.timestamps(time_column)?;
+        let cutoff_millis = cutoff.and_utc().timestamp_millis();
+        let test_rows = millis.iter().filter(|millis| **millis >= cutoff_millis).count();
+        let split = SplitInfo {
+            method: "train_test_split_at".to_string(),
+            test_ratio: test_rows as f32 / millis.len().max(1) as f32,
+            time_column: Some(time_column.to_string()),
+            cutoff: Some(cutoff.to_string()),
+            ..Default::default()
+        };
+        parsed.split_at_millis(&millis, cutoff_millis, split)
+    }
+
+    /// Split into `train` and `test`, where `test` holds the rows in the last `test_ratio` of the
+    /// time span covered by `time_column`, rather than the last `test_ratio` of the rows.
+    pub fn train_test_split_by_time(&self, time_column: &str, test_ratio: f32) -> Result<DatasetDict, Box<dyn Error>> {
+        if !(test_ratio > 0.0 && test_ratio < 1.0) {
+            return Err(format!("test_ratio must be strictly between 0 and 1, got {}", test_ratio).into());
+        }
+        let (parsed, millis) = self.timestamps(time_column)?;
+        let (first, last) = match (millis.iter().min(), millis.iter().max()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Err("Cannot split an empty dataset by time".into()),
+        };
+        let cutoff = last - ((last - first) as f64 * test_ratio as f64).round() as i64;
+
+        let split = SplitInfo {
+            method: "train_test_split_by_time".to_string(),
+            test_ratio,
+            time_column: Some(time_column.to_string()),
+            cutoff: DateTime::from_timestamp_millis(cutoff).map(|cutoff| cutoff.naive_utc().to_string()),
+            ..Default::default()
+        };
+        parsed.split_at_millis(&millis, cutoff, split)
+    }
+
+    /// Rolling-origin backtesting folds over the rows in time order.
+    ///
+    /// Each validation window follows its training window, so every fold trains on the past and
+    /// validates on the future; the windows move forward by `test_size` rows per fold. Views index
+    /// into this dataset, so parse `time_column` with `parse_datetime` first to get it as a
+    /// `Datetime` column in the folds.
+    pub fn rolling_origin(&self, time_column: &str, options: &RollingOriginOptions) -> Result<Folds<'_>, Box<dyn Error>> {
+        let (_, millis) = self.timestamps(time_column)?;
+        let n = millis.len();
+        if options.n_splits == 0 {
+            return Err("Rolling-origin validation needs at least 1 fold".into());
+        }
+        let test_size = options.test_size.unwrap_or(n / (options.n_splits + 1));
+        if test_size == 0 || options.n_splits * test_size + options.gap >= n {
+            return Err(format!(
+                "Cannot make {} validation windows of {} rows with a gap of {} out of {} rows",
+                options.n_splits, test_size, options.gap, n
+            )
+            .into());
+        }
+
+        let order = chronological_order(&millis);
+        let mut windows = Vec::with_capacity(options.n_splits);
+        for fold in 0..options.n_splits {
+            let validation_start = n - (options.n_splits - fold) * test_size;
+            let train_end = validation_start - options.gap;
+            let train_start = options.max_train_size.map_or(0, |size| train_end.saturating_sub(size));
+            windows.push((
+                order[train_start..train_end].to_vec(),
+                order[validation_start..validation_start + test_size].to_vec(),
+            ));
+        }
+        Ok(Folds::from_windows(self, windows))
+    }
+}
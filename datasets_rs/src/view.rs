@@ -0,0 +1,178 @@
+//! # Row Selection
+//!
+//! This module filters, selects, sorts, deduplicates and samples the rows of a `DataSet`. Each
+//! operation is available on `DataSetView`, which only maps row indices into the borrowed
+//! dataset like the indices mapping of Hugging Face datasets: chained operations compose their
+//! indices and no column is copied until the view is materialized with `to_dataset` or written
+//! out with `save_data`. The `DataSet` methods of the same names materialize the view at once,
+//! keeping the dataset's `uuid`, `timestamp` and `info`, and all but `filter` cache their results
+//! (see `cache`).
+
+use polars::prelude::*;
+use std::error::Error;
+use std::path::Path;
+
+use crate::cache::params_key;
+use crate::cross_validation::DataSetView;
+use crate::load_dataset::DataSet;
+use crate::map::{row_at, Row};
+use crate::split::{permutation, take_rows};
+
+/// Name of the column holding view positions while sorting or deduplicating.
+const POSITION_COLUMN: &str = "__view_position";
+
+/// Rows to take for a `frac` sample of `n` rows, rounded to the nearest row.
+fn frac_rows(frac: f64, n: usize) -> Result<usize, Box<dyn Error>> {
+    if !(0.0..=1.0).contains(&frac) {
+        return Err(format!("Sample fraction must be between 0 and 1, got {}", frac).into());
+    }
+    Ok((frac * n as f64).round() as usize)
+}
+
+impl<'a> DataSetView<'a> {
+    /// A view of the rows at `indices` of this view, in that order.
+    pub fn select(&self, indices: &[usize]) -> Result<Self, Box<dyn Error>> {
+        let mut rows = Vec::with_capacity(indices.len());
+        for index in indices {
+            match self.rows.get(*index) {
+                Some(row) => rows.push(*row),
+                None => return Err(format!("Row {} is out of range for {} rows", index, self.len()).into()),
+            }
+        }
+        Ok(DataSetView { rows, ..self.clone() })
+    }
+
+    /// A view of the first `n` rows, or of all rows if there are fewer.
+    pub fn take(&self, n: usize) -> Self {
+        DataSetView { rows: self.rows[..n.min(self.len())].to_vec(), ..self.clone() }
+    }
+
+    /// A view of the rows where the boolean `predicate` is true; null counts as false.
+    pub fn filter(&self, predicate: Expr) -> Result<Self, Box<dyn Error>> {
+        let mask = self.dataset.data.clone().lazy().select([predicate.alias("mask")]).collect()?;
+        let mask = mask.column("mask")?.bool()?.clone();
+        let height = self.dataset.data.height();
+        if mask.len() != height {
+            return Err(format!("Filter predicate gave {} values for {} rows", mask.len(), height).into());
+        }
+        let rows = self.rows.iter().copied().filter(|row| mask.get(*row as usize).unwrap_or(false)).collect();
+        Ok(DataSetView { rows, ..self.clone() })
+    }
+
+    /// A view of the rows for which `predicate` returns true.
+    pub fn filter_rows<F: Fn(&Row) -> bool>(&self, predicate: F) -> Result<Self, Box<dyn Error>> {
+        let mut rows = Vec::new();
+        for row in &self.rows {
+            if predicate(&row_at(&self.dataset.data, *row as usize)) {
+                rows.push(*row);
+            }
+        }
+        Ok(DataSetView { rows, ..self.clone() })
+    }
+
+    /// The `columns` of the view's rows, with the position of each row in the view.
+    fn keys(&self, columns: &[String]) -> Result<DataFrame, Box<dyn Error>> {
+        let keys = take_rows(&self.dataset.data.select(columns)?, self.rows.clone())?;
+        Ok(keys.with_row_index(POSITION_COLUMN, None)?)
+    }
+
+    /// The view's rows at the positions in the `POSITION_COLUMN` of `keys`.
+    fn rows_at(&self, keys: &DataFrame) -> Result<Vec<IdxSize>, Box<dyn Error>> {
+        let positions = keys.column(POSITION_COLUMN)?.idx()?;
+        Ok(positions.into_no_null_iter().map(|position| self.rows[position as usize]).collect())
+    }
+
+    /// A view sorted by `columns`, in order of priority; equal rows keep their order and nulls
+    /// come last.
+    pub fn sort(&self, columns: &[&str], descending: bool) -> Result<Self, Box<dyn Error>> {
+        if columns.is_empty() {
+            return Err("Sort needs at least one column".into());
+        }
+        let columns: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+        let options = SortMultipleOptions::default()
+            .with_order_descending(descending)
+            .with_nulls_last(true)
+            .with_maintain_order(true);
+        let sorted = self.keys(&columns)?.sort(columns, options)?;
+        Ok(DataSetView { rows: self.rows_at(&sorted)?, ..self.clone() })
+    }
+
+    /// A view keeping the first of each set of rows with equal values in `columns`, or in all
+    /// columns if `None`.
+    pub fn unique(&self, columns: Option<&[&str]>) -> Result<Self, Box<dyn Error>> {
+        let columns: Vec<String> = match columns {
+            Some(columns) => columns.iter().map(|column| column.to_string()).collect(),
+            None => self.dataset.data.get_column_names().iter().map(|column| column.to_string()).collect(),
+        };
+        let unique = self.keys(&columns)?.unique_stable(Some(&columns), UniqueKeepStrategy::First, None)?;
+        Ok(DataSetView { rows: self.rows_at(&unique)?, ..self.clone() })
+    }
+
+    /// A view of `n` rows drawn at random without replacement; the same seed always gives the
+    /// same rows.
+    pub fn sample(&self, n: usize, seed: u64) -> Result<Self, Box<dyn Error>> {
+        if n > self.len() {
+            return Err(format!("Cannot sample {} rows from a dataset of {} rows", n, self.len()).into());
+        }
+        let rows = permutation(self.len(), seed)[..n].iter().map(|position| self.rows[*position as usize]).collect();
+        Ok(DataSetView { rows, seed: Some(seed), ..self.clone() })
+    }
+
+    /// A view of a `frac` share of the rows drawn at random, as by `sample`.
+    pub fn sample_frac(&self, frac: f64, seed: u64) -> Result<Self, Box<dyn Error>> {
+        self.sample(frac_rows(frac, self.len())?, seed)
+    }
+
+    /// Write the view's rows to a file, copying them only now; see `DataSet::save_data`.
+    pub fn save_data<P: AsRef<Path>>(&self, file_path: P, file_extension: &str) -> Result<(), Box<dyn Error>> {
+        self.to_dataset()?.save_data(file_path, file_extension)
+    }
+}
+
+impl DataSet {
+    /// A view of all rows, to select rows from without copying them.
+    pub fn view(&self) -> DataSetView<'_> {
+        DataSetView::new(self, (0..self.data.height() as IdxSize).collect())
+    }
+
+    /// The rows at `indices`, in that order.
+    pub fn select(&self, indices: &[usize]) -> Result<DataSet, Box<dyn Error>> {
+        self.cached("select", &params_key(indices), || self.view().select(indices)?.to_dataset())
+    }
+
+    /// The first `n` rows, or all rows if there are fewer.
+    pub fn take(&self, n: usize) -> DataSet {
+        let mut taken = self.with_data(self.data.head(Some(n)));
+        taken.fingerprint = self.derive_fingerprint("take", &params_key(&n));
+        taken
+    }
+
+    /// The rows where the boolean `predicate` is true, such as `col("bytes").gt(lit(1_000_000))`.
+    ///
+    /// An expression cannot be fingerprinted reliably, so the result is neither cached nor
+    /// fingerprinted.
+    pub fn filter(&self, predicate: Expr) -> Result<DataSet, Box<dyn Error>> {
+        self.view().filter(predicate)?.to_dataset()
+    }
+
+    /// The rows for which `predicate` returns true.
+    pub fn filter_rows<F: Fn(&Row) -> bool>(&self, predicate: F) -> Result<DataSet, Box<dyn Error>> {
+        self.view().filter_rows(predicate)?.to_dataset()
+    }
+
+    /// The rows sorted by `columns`; see `DataSetView::sort`.
+    pub fn sort(&self, columns: &[&str], descending: bool) -> Result<DataSet, Box<dyn Error>> {
+        self.cached("sort", &params_key(&(columns, descending)), || self.view().sort(columns, descending)?.to_dataset())
+    }
+
+    /// The rows without duplicates in `columns`, or in all columns if `None`; see
+    /// `DataSetView::unique`.
+    pub fn unique(&self, columns: Option<&[&str]>) -> Result<DataSet, Box<dyn Error>> {
+        self.cached("unique", &params_key(&columns), || self.view().unique(columns)?.to_dataset())
+    }
+
+    /// Draw a `frac` share of the rows at random, as by `sample`.
+    pub fn sample_frac(&self, frac: f64, seed: u64) -> Result<DataSet, Box<dyn Error>> {
+        self.sample(frac_rows(frac, self.data.height())?, seed)
+    }
+}
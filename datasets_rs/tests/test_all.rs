@@ -7,6 +7,31 @@ use uuidv4 as id_q;
 mod tests {
     use super::*;
 
+    /// A scratch directory for one test, unique to this process and removed when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("datasets_rs_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &std::path::Path {
+            &self.0
+        }
+
+        fn join<P: AsRef<std::path::Path>>(&self, path: P) -> std::path::PathBuf {
+            self.0.join(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
     #[test]
     fn test_dataloader() {
         let dataset = ParquetSecurityDataset {
@@ -77,4 +102,1052 @@ fn test_handling_empty_input_data() {
     assert_eq!(predictions_simd.extract(1), 0.0);
 }
 
-}
\ No newline at end of file
+#[test]
+fn test_load_json_infers_nested_schema() {
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let dir = TempDir::new("nested_json");
+    let path = dir.join("nested.json");
+    std::fs::write(
+        &path,
+        r#"[{"host": "a", "ports": [22, 80], "geo": {"country": "NL"}},
+            {"host": "b", "ports": [], "geo": {"country": "DE"}, "score": 0.5}]"#,
+    )
+    .unwrap();
+
+    let df = DataSet::load_json(&path, None).unwrap();
+    assert_eq!(df.height(), 2);
+    assert!(matches!(df.column("ports").unwrap().dtype(), DataType::List(_)));
+    assert!(matches!(df.column("geo").unwrap().dtype(), DataType::Struct(_)));
+    assert_eq!(df.column("score").unwrap().null_count(), 1);
+
+    let hints = Schema::from_iter([Field::new("score", DataType::Float32)]);
+    let df = DataSet::load_json(&path, Some(&hints)).unwrap();
+    assert_eq!(df.column("score").unwrap().dtype(), &DataType::Float32);
+}
+
+#[test]
+fn test_lazy_scan_and_split() {
+    use datasets_rs::csv_options::{CsvOptions, TextEncoding};
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let dir = TempDir::new("lazy");
+    let path = dir.join("flows.csv");
+    let bytes = |flow: i64| if flow % 4 == 0 { "-".to_string() } else { (flow * 100).to_string() };
+    let rows: String = (0..20).map(|flow| format!("{};{}\n", flow, bytes(flow))).collect();
+    std::fs::write(&path, format!("# exported 2026-10-01\n{}", rows)).unwrap();
+
+    // The CSV dialect is applied by the lazy scan as by load_data_with.
+    let options = CsvOptions {
+        has_header: false,
+        comment_prefix: Some("#".to_string()),
+        null_values: vec!["-".to_string()],
+        ..Default::default()
+    };
+    let lazy = DataSet::scan_with(&path, &options).unwrap();
+    assert_eq!(lazy.height().unwrap(), 20);
+    let eager = DataSet::new(DataSet::load_data_with(&path, &options).unwrap());
+    assert!(lazy.collect().unwrap().data.equals_missing(&eager.data));
+    let large = lazy.filter(col("column_2").gt(lit(1000))).collect().unwrap();
+    assert_eq!(large.data.height(), 7);
+
+    // A seeded lazy split hashes rows into train or test; without a seed the last rows are the test set.
+    let (train, test) = lazy.train_test_split(0.25, Some(9)).unwrap();
+    let flows_of = |lazy: &datasets_rs::lazy_dataset::LazyDataSet| -> Vec<i64> {
+        let data = lazy.collect().unwrap().data;
+        data.column("column_1").unwrap().i64().unwrap().into_no_null_iter().collect()
+    };
+    let (train_flows, test_flows) = (flows_of(&train), flows_of(&test));
+    let mut all_flows: Vec<i64> = train_flows.iter().chain(&test_flows).copied().collect();
+    all_flows.sort();
+    assert_eq!(all_flows, (0..20).collect::<Vec<i64>>());
+    let (same_train, _) = lazy.train_test_split(0.25, Some(9)).unwrap();
+    assert_eq!(flows_of(&same_train), train_flows);
+    assert_eq!(test.info.split.as_ref().unwrap().seed, Some(9));
+    let many = DataSet::new(df!("flow" => (0..10_000i64).collect::<Vec<_>>()).unwrap()).lazy();
+    let (_, many_test) = many.train_test_split(0.25, Some(3)).unwrap();
+    let many_test = many_test.data.with_streaming(true).collect().unwrap();
+    assert!((2300..2700).contains(&many_test.height()), "{}", many_test.height());
+    let (_, test) = lazy.train_test_split(0.25, None).unwrap();
+    let test = test.collect().unwrap();
+    let flows: Vec<i64> = test.data.column("column_1").unwrap().i64().unwrap().into_no_null_iter().collect();
+    assert_eq!(flows, vec![15, 16, 17, 18, 19]);
+    assert!(lazy.train_test_split(1.5, None).is_err());
+
+    train.save_data(dir.join("train.parquet"), "parquet").unwrap();
+    assert_eq!(DataSet::load_data(dir.join("train.parquet")).unwrap().height(), train_flows.len());
+    let latin1 = CsvOptions { encoding: TextEncoding::Latin1, ..options };
+    assert!(DataSet::scan_with(&path, &latin1).is_err());
+}
+
+#[test]
+fn test_ndjson_batches() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::ndjson::{read_ndjson, write_ndjson, NdjsonBatches};
+    use polars::prelude::*;
+    use std::io::Cursor;
+
+    let df = df!(
+        "flow" => [1i64, 2, 3, 4, 5],
+        "host" => ["web1", "db1", "web2", "db2", "web3"],
+        "bytes" => [Some(500i64), Some(20), None, None, Some(90)]
+    )
+    .unwrap();
+    let mut buf = Vec::new();
+    write_ndjson(&df, &mut buf).unwrap();
+    buf.extend_from_slice(b"\n\n");
+
+    // The second batch only has nulls in `bytes`; stacking the batches restores its dtype.
+    let batches: Vec<DataFrame> = NdjsonBatches::new(Cursor::new(&buf), 2).map(Result::unwrap).collect();
+    assert_eq!(batches.iter().map(DataFrame::height).collect::<Vec<_>>(), vec![2, 2, 1]);
+    assert!(read_ndjson(Cursor::new(&buf), 2).unwrap().equals_missing(&df));
+    let fixed = NdjsonBatches::new(Cursor::new(&buf), 2).with_schema(Arc::new(df.schema()));
+    assert!(fixed.map(Result::unwrap).all(|batch| batch.schema() == df.schema()));
+
+    // A key first seen after the first batch is kept, and a column widened later is cast.
+    let late = b"{\"flow\": 1, \"bytes\": 500}\n{\"flow\": 2, \"bytes\": 20}\n{\"flow\": 3, \"bytes\": 0.5, \"tag\": \"scan\"}\n";
+    let read = read_ndjson(Cursor::new(&late[..]), 2).unwrap();
+    assert_eq!(read.get_column_names(), vec!["flow", "bytes", "tag"]);
+    assert_eq!(read.column("bytes").unwrap().dtype(), &DataType::Float64);
+    assert_eq!(read.column("tag").unwrap().str().unwrap().get(2), Some("scan"));
+    assert_eq!(read.column("tag").unwrap().null_count(), 2);
+
+    let dir = TempDir::new("ndjson");
+    DataSet::new(df.clone()).save_data(dir.join("flows.jsonl"), "jsonl").unwrap();
+    assert!(DataSet::load_data(dir.join("flows.jsonl")).unwrap().equals_missing(&df));
+}
+
+#[test]
+fn test_sniff_content() {
+    use datasets_rs::compression::Compression;
+    use datasets_rs::sniff::{sniff_compression, sniff_content};
+
+    assert_eq!(sniff_content(b"PAR1\x15\x04").unwrap().format, "parquet");
+    assert_eq!(sniff_content(b"ARROW1\0\0").unwrap().format, "arrow");
+    assert_eq!(sniff_content(b"[{\"a\": 1}]").unwrap().format, "json");
+    assert_eq!(sniff_content(b"{\"a\": 1}\n{\"a\": 2}\n").unwrap().format, "jsonl");
+    assert_eq!(sniff_content(b"{\n  \"a\": 1\n}").unwrap().format, "json");
+    assert_eq!(sniff_content(b"[\n  {\"a\": 1}\n]").unwrap().format, "json");
+    assert_eq!(sniff_content(b"[]").unwrap().format, "json");
+    let bracketed = sniff_content(b"[timestamp],src,dst\n2026-10-01,10.0.0.1,10.0.0.2\n").unwrap();
+    assert_eq!((bracketed.format, bracketed.separator), ("csv", b','));
+
+    let tsv = sniff_content(b"ip\tport\n10.0.0.1\t22\n10.0.0.2\t80\n").unwrap();
+    assert_eq!((tsv.format, tsv.separator), ("csv", b'\t'));
+    let semicolon = sniff_content(b"ip;port\n10.0.0.1;22\n10.0.0.2;80\n").unwrap();
+    assert_eq!(semicolon.separator, b';');
+
+    assert_eq!(sniff_compression(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+    assert_eq!(sniff_compression(&[0x28, 0xb5, 0x2f, 0xfd]), Compression::Zstd);
+    assert_eq!(sniff_compression(b"ip,port"), Compression::None);
+}
+
+#[test]
+fn test_load_data_as_forced_format() {
+    use datasets_rs::csv_options::CsvOptions;
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::sniff::detect_format;
+
+    // A CSV header starting with `[[` sniffs as JSON, but can be read as the CSV it is.
+    let dir = TempDir::new("forced_format");
+    let path = dir.join("export.txt");
+    std::fs::write(&path, "[[id]];name\n1;web1\n2;db1\n").unwrap();
+    assert_eq!(detect_format(&path).unwrap().format, "json");
+    let df = DataSet::load_data_as(&path, "csv", &CsvOptions::default()).unwrap();
+    assert_eq!(df.get_column_names(), vec!["[[id]]", "name"]);
+    assert_eq!(df.height(), 2);
+
+    let parquet = dir.join("flows.bin");
+    DataSet::new(df.clone()).save_data(&parquet, "parquet").unwrap();
+    assert!(DataSet::load_data_as(&parquet, "parquet", &CsvOptions::default()).unwrap().equals_missing(&df));
+    assert!(DataSet::load_data_as(&parquet, "xml", &CsvOptions::default()).is_err());
+}
+
+#[test]
+fn test_compressed_round_trip() {
+    use datasets_rs::compression::{open_decompressed, CompressedWriter, Compression};
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::sniff::sniff_compression;
+    use polars::prelude::*;
+    use std::io::{Read, Write};
+
+    let dir = TempDir::new("compressed");
+    let text = "ip,port\n10.0.0.1,22\n10.0.0.2,80\n".repeat(100);
+    for compression in [Compression::Gzip, Compression::Zstd, Compression::Bzip2, Compression::Xz] {
+        let path = dir.join(format!("flows.csv.{}", compression.extension()));
+        let mut writer = CompressedWriter::create(&path, compression).unwrap();
+        writer.write_all(text.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.len() < text.len());
+        assert_eq!(sniff_compression(&bytes), compression);
+
+        let mut read = String::new();
+        open_decompressed(&path, compression).unwrap().read_to_string(&mut read).unwrap();
+        assert_eq!(read, text);
+    }
+
+    let dataset = DataSet::new(df!("port" => [22i64, 80, 443], "host" => ["web1", "db1", "web2"]).unwrap());
+    for format in ["csv.gz", "csv.zst", "csv.bz2", "csv.xz"] {
+        let path = dir.join(format!("saved.{}", format));
+        dataset.save_data(&path, format).unwrap();
+        assert!(DataSet::load_data(&path).unwrap().equals(&dataset.data), "{}", format);
+    }
+    // Parquet and Arrow compress their own pages instead.
+    assert!(dataset.save_data(dir.join("saved.parquet.zst"), "parquet.zst").is_err());
+}
+
+#[test]
+fn test_load_many_files() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::multi_file::{LoadManyOptions, SOURCE_FILE_COLUMN};
+    use polars::prelude::*;
+
+    let dir = TempDir::new("multi_file");
+    std::fs::create_dir_all(dir.join("2026-10-01")).unwrap();
+    std::fs::create_dir_all(dir.join("2026-10-02")).unwrap();
+    let first = DataSet::new(df!("port" => [22i32, 80]).unwrap());
+    first.save_data(dir.join("2026-10-01/flows.parquet"), "parquet").unwrap();
+    let second = DataSet::new(df!("port" => [443i64], "label" => ["attack"]).unwrap());
+    second.save_data(dir.join("2026-10-02/flows.csv"), "csv").unwrap();
+    // Hidden files and directories, such as editor backups or `.git`, are skipped.
+    std::fs::create_dir_all(dir.join(".git")).unwrap();
+    first.save_data(dir.join(".git/flows.csv"), "csv").unwrap();
+    first.save_data(dir.join("2026-10-01/.flows.csv"), "csv").unwrap();
+
+    // A directory is walked recursively and the schemas of its files are reconciled.
+    let options = LoadManyOptions { add_source_column: true, ..Default::default() };
+    let df = DataSet::load_many(&[dir.path().to_string_lossy()], &options).unwrap();
+    assert_eq!(df.shape(), (3, 3));
+    assert_eq!(df.column("port").unwrap().dtype(), &DataType::Int64);
+    assert_eq!(df.column("label").unwrap().null_count(), 2);
+    let sources: Vec<&str> = df.column(SOURCE_FILE_COLUMN).unwrap().str().unwrap().into_no_null_iter().collect();
+    assert!(sources[0].ends_with("flows.parquet") && sources[2].ends_with("flows.csv"), "{:?}", sources);
+    assert_eq!(DataSet::load_data(dir.path()).unwrap().height(), 3);
+
+    let pattern = dir.join("*/*.parquet").to_string_lossy().to_string();
+    let df = DataSet::load_many(&[pattern], &LoadManyOptions::default()).unwrap();
+    assert_eq!(df.shape(), (2, 1));
+}
+
+#[test]
+fn test_arrow_ipc_round_trip() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::sniff::sniff_content;
+    use polars::prelude::*;
+
+    let dir = TempDir::new("arrow");
+    let dataset = DataSet::new(
+        df!(
+            "host" => ["web1", "db1", "web2"],
+            "port" => [22u16, 80, 443],
+            "bytes" => [Some(500i64), None, Some(7000)]
+        )
+        .unwrap(),
+    );
+    for (file, format) in [("flows.arrow", "arrow"), ("flows.feather", "feather"), ("flows.arrows", "arrows")] {
+        let path = dir.join(file);
+        dataset.save_data(&path, format).unwrap();
+        let head = std::fs::read(&path).unwrap();
+        let expected = if format == "arrows" { "arrows" } else { "arrow" };
+        assert_eq!(sniff_content(&head).unwrap().format, expected);
+        assert!(DataSet::load_data(&path).unwrap().equals_missing(&dataset.data), "{}", file);
+    }
+
+    // The IPC file format can also be scanned lazily.
+    let scanned = DataSet::scan(dir.join("flows.arrow")).unwrap().filter(col("port").gt(lit(50))).collect().unwrap();
+    assert_eq!(scanned.data.height(), 2);
+}
+
+#[test]
+fn test_save_to_disk_round_trip() {
+    use datasets_rs::dataset_dict::DatasetDict;
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let dir = TempDir::new("save_to_disk");
+    let mut dataset = DataSet::new(
+        df!(
+            "text" => ["ssh brute force", "port scan", "benign", "dns tunnel", "benign"],
+            "label" => [Some(1i64), Some(1), Some(0), None, Some(0)],
+            "score" => [0.9f64, 0.7, 0.1, 0.8, 0.2]
+        )
+        .unwrap(),
+    );
+    dataset.info.description = "Labelled alerts".to_string();
+
+    // A single dataset spread over three shards.
+    dataset.save_to_disk(dir.join("alerts"), 3).unwrap();
+    for shard in 0..3 {
+        assert!(dir.join(format!("alerts/data-{:05}-of-00003.arrow", shard)).is_file());
+    }
+    let loaded = DataSet::load_from_disk(dir.join("alerts")).unwrap();
+    assert!(loaded.data.equals_missing(&dataset.data));
+    assert_eq!(loaded.info.description, "Labelled alerts");
+    assert!(loaded.info.features.is_some());
+    assert_eq!(loaded.fingerprint, Some(dataset.fingerprint().unwrap()));
+
+    // A dataset dict keeps its splits, and cannot be loaded as a single dataset.
+    let mut splits = DatasetDict::new();
+    splits.insert("train", dataset.select(&[0, 1, 2]).unwrap());
+    splits.insert("test", dataset.select(&[3, 4]).unwrap());
+    splits.save_to_disk(dir.join("dict"), 1).unwrap();
+    let loaded = DatasetDict::load_from_disk(dir.join("dict")).unwrap();
+    assert_eq!(loaded.split_names(), splits.split_names());
+    for (name, split) in splits.iter() {
+        assert!(loaded.get(name).unwrap().data.equals_missing(&split.data), "{}", name);
+    }
+    assert!(DataSet::load_from_disk(dir.join("dict")).is_err());
+}
+
+#[test]
+fn test_parse_readme_configs() {
+    use datasets_rs::hub::{is_repo_id, parse_readme_configs};
+
+    let readme = "---\nconfigs:\n- config_name: default\n  data_files:\n  - split: train\n    path: data/train-*\n  - split: test\n    path: data/test.csv\n- config_name: all\n  data_files: data/*.csv\n---\n# Flows\n";
+    let configs = parse_readme_configs(readme).unwrap();
+    assert_eq!(configs.len(), 2);
+    assert_eq!(configs[0].data_files["train"], vec!["data/train-*"]);
+    assert_eq!(configs[0].data_files["test"], vec!["data/test.csv"]);
+    assert_eq!(configs[1].data_files["train"], vec!["data/*.csv"]);
+    assert!(parse_readme_configs("# No header").unwrap().is_empty());
+
+    assert!(is_repo_id("acme/netflows"));
+    assert!(!is_repo_id("data/netflows.csv"));
+    assert!(!is_repo_id("/data/netflows"));
+}
+
+#[test]
+fn test_download_uses_cache_offline() {
+    use datasets_rs::download::{download, download_many, DownloadOptions};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let body = "ip,port\n10.0.0.1,22\n10.0.0.2,80\n";
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/flows.csv", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut stream = listener.incoming().next().unwrap().unwrap();
+        let mut request = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while request.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(stream, "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    });
+
+    let cache_dir = TempDir::new("download");
+    let options = DownloadOptions { cache_dir: cache_dir.path().to_path_buf(), offline: false, sha256: None, concurrency: 1, retries: 1 };
+    let blob = download(&url, &options).unwrap();
+    assert_eq!(std::fs::read_to_string(&blob).unwrap(), body);
+
+    // The server only answers once; offline mode must be served from the cache.
+    let offline = DownloadOptions { offline: true, ..options };
+    assert_eq!(download(&url, &offline).unwrap(), blob);
+
+    // A failed download keeps its underlying error.
+    let closed = format!("http://{}/flows.csv", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+    let error = download_many(&[url.as_str(), closed.as_str()], &offline).unwrap_err();
+    assert!(error.to_string().contains("is not in the download cache"), "{}", error);
+    let online = DownloadOptions { offline: false, ..offline };
+    assert!(download_many(&[closed], &online).unwrap_err().downcast_ref::<ureq::Error>().is_some());
+}
+
+#[test]
+fn test_concurrent_downloads_of_one_url() {
+    use datasets_rs::download::{download, DownloadOptions};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let body: String = (0..4000).map(|i| format!("10.0.{}.{},{}\n", i / 256, i % 256, i)).collect();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/flows.csv", listener.local_addr().unwrap());
+    let served = body.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            let (mut stream, body) = (stream.unwrap(), served.clone());
+            std::thread::spawn(move || {
+                let mut request = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while request.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+                // Trickle the body out so that both downloads are writing at the same time.
+                for chunk in body.as_bytes().chunks(body.len() / 8 + 1) {
+                    stream.write_all(chunk).unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+            });
+        }
+    });
+
+    let cache_dir = TempDir::new("concurrent_download");
+    let options = DownloadOptions { cache_dir: cache_dir.path().to_path_buf(), offline: false, sha256: None, concurrency: 1, retries: 1 };
+    let blobs: Vec<_> = std::thread::scope(|scope| {
+        let downloads: Vec<_> = (0..2).map(|_| scope.spawn(|| download(&url, &options).unwrap())).collect();
+        downloads.into_iter().map(|download| download.join().unwrap()).collect()
+    });
+    for blob in &blobs {
+        assert_eq!(std::fs::read_to_string(blob).unwrap(), body);
+    }
+    // Neither the lock nor a download's own part file is left behind.
+    assert_eq!(std::fs::read_dir(cache_dir.join("incomplete")).unwrap().count(), 0);
+}
+
+#[test]
+fn test_load_csv_dialect() {
+    use datasets_rs::csv_options::{CsvOptions, TextEncoding};
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let dir = TempDir::new("csv_dialect");
+    let path = dir.join("firewall_export.csv");
+    // Headerless, semicolon-delimited, Latin-1 with backslash-escaped quotes and `-` for nulls.
+    std::fs::write(
+        &path,
+        b"# exported 2026-10-01\n10.0.0.1;22;\"Z\xfcrich \\\"HQ\\\"\";-\n10.0.0.2;80;\"M\xfcnchen\";0.5\n",
+    )
+    .unwrap();
+
+    let options = CsvOptions {
+        escape_char: Some(b'\\'),
+        has_header: false,
+        comment_prefix: Some("#".to_string()),
+        null_values: vec!["-".to_string()],
+        encoding: TextEncoding::Latin1,
+        dtypes: vec![("column_2".to_string(), DataType::UInt16)],
+        ..Default::default()
+    };
+    let df = DataSet::load_data_with(&path, &options).unwrap();
+    assert_eq!(df.shape(), (2, 4));
+    assert_eq!(df.column("column_2").unwrap().dtype(), &DataType::UInt16);
+    assert_eq!(df.column("column_3").unwrap().str().unwrap().get(0), Some("Z\u{fc}rich \"HQ\""));
+    assert_eq!(df.column("column_4").unwrap().null_count(), 1);
+
+    // Reading a file as CSV without a separator sniffs it, as when the format is detected.
+    assert!(options.read_file_sniffed(&path).unwrap().equals_missing(&df));
+    let semicolons = dir.join("ports.csv");
+    std::fs::write(&semicolons, "ip;port\n10.0.0.1;22\n10.0.0.2;80\n").unwrap();
+    assert_eq!(CsvOptions::default().read_file_sniffed(&semicolons).unwrap().shape(), (2, 2));
+}
+
+#[test]
+fn test_dataset_dict_round_trip() {
+    use datasets_rs::dataset_dict::DatasetDict;
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let df = df!("port" => (0..10).collect::<Vec<i64>>()).unwrap();
+    let splits = DataSet::new(df.clone()).train_test_split(0.3, None).unwrap();
+    assert_eq!(splits.split_names(), vec!["train", "test"]);
+    assert_eq!(splits["test"].data.height(), 3);
+
+    let filtered = splits.filter(col("port").gt(lit(5))).unwrap();
+    assert_eq!(filtered["train"].data.height(), 1);
+
+    // The manifest keeps each split's info and fingerprint.
+    let mut splits = DataSet::new(df).train_test_split(0.3, Some(3)).unwrap();
+    splits.get_mut("train").unwrap().fingerprint = Some("flows-train".to_string());
+    let dir = TempDir::new("splits");
+    splits.save_data(dir.path(), "parquet").unwrap();
+    let loaded = DatasetDict::load_data(dir.path()).unwrap();
+    assert_eq!(loaded.split_names(), vec!["train", "test"]);
+    assert_eq!(loaded.num_rows(), 10);
+    assert_eq!(loaded["test"].info.split.as_ref().and_then(|split| split.seed), Some(3));
+    assert_eq!(loaded["test"].info.split, splits["test"].info.split);
+    assert_eq!(loaded["train"].fingerprint.as_deref(), Some("flows-train"));
+
+    // Without a manifest, each file is the split named by its file name without extensions.
+    let files = TempDir::new("split_files");
+    for (name, format) in [("train.csv", "csv"), ("train.v2.csv", "csv"), ("test.csv.gz", "csv.gz")] {
+        splits["test"].save_data(files.join(name), format).unwrap();
+    }
+    assert_eq!(DatasetDict::load_data(files.path()).unwrap().split_names(), vec!["train", "test", "train.v2"]);
+    splits["test"].save_data(files.join("test.parquet"), "parquet").unwrap();
+    assert!(DatasetDict::load_data(files.path()).is_err());
+}
+
+#[test]
+fn test_stratified_train_test_split() {
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let labels: Vec<&str> = (0..50).map(|i| if i % 10 == 0 { "ddos" } else { "benign" }).collect();
+    let df = df!("flow" => (0..50).collect::<Vec<i64>>(), "label" => labels).unwrap();
+    let splits = DataSet::new(df).stratified_train_test_split("label", 0.2, None).unwrap();
+    let count = |split: &str, label: &str| {
+        let labels = splits[split].data.column("label").unwrap().str().unwrap().clone();
+        labels.into_iter().filter(|l| *l == Some(label)).count()
+    };
+    assert_eq!((count("train", "ddos"), count("test", "ddos")), (4, 1));
+    assert_eq!((count("train", "benign"), count("test", "benign")), (36, 9));
+
+    // A class with a single row cannot appear in both splits.
+    let rare = df!("label" => ["benign", "benign", "benign", "portscan"]).unwrap();
+    assert!(DataSet::new(rare).stratified_train_test_split("label", 0.25, None).is_err());
+}
+
+#[test]
+fn test_seeded_split_is_reproducible() {
+    use datasets_rs::dataloader::DataLoader;
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let dataset = DataSet::new(df!("flow" => (0..100).collect::<Vec<i64>>()).unwrap());
+    let first = dataset.train_test_split(0.2, Some(42)).unwrap();
+    let second = dataset.train_test_split(0.2, Some(42)).unwrap();
+    assert!(first["test"].data.equals(&second["test"].data));
+    assert_eq!(first["test"].info.split.as_ref().unwrap().seed, Some(42));
+
+    // The recorded split parameters regenerate the same split.
+    let split = first["train"].info.split.clone().unwrap();
+    let regenerated = dataset.train_test_split(split.test_ratio, split.seed).unwrap();
+    assert!(regenerated["train"].data.equals(&first["train"].data));
+
+    let loader = DataLoader::new(dataset, 32, Some(7));
+    let batches: Vec<DataFrame> = loader.epoch(3).map(Result::unwrap).collect();
+    let replayed: Vec<DataFrame> = loader.epoch(3).map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 4);
+    assert!(batches.iter().zip(&replayed).all(|(a, b)| a.equals(b)));
+}
+
+#[test]
+fn test_cross_validation_folds() {
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let labels: Vec<&str> = (0..20).map(|i| if i % 4 == 0 { "attack" } else { "benign" }).collect();
+    let hosts: Vec<&str> = (0..20).map(|i| ["a", "b", "c"][i % 3]).collect();
+    let df = df!("bytes" => (0..20).map(|i| i as f64).collect::<Vec<f64>>(), "label" => labels, "host" => hosts).unwrap();
+    let dataset = DataSet::new(df);
+
+    // Every row is validated exactly once over the k folds.
+    let folds: Vec<_> = dataset.k_fold(5, Some(1)).unwrap().collect();
+    assert_eq!(folds.len(), 5);
+    let mut validated: Vec<_> = folds.iter().flat_map(|(_, validation)| validation.rows().to_vec()).collect();
+    validated.sort_unstable();
+    assert_eq!(validated, (0..20).collect::<Vec<IdxSize>>());
+    assert!(folds.iter().all(|(train, validation)| train.len() == 16 && validation.len() == 4));
+
+    for (_, validation) in dataset.stratified_k_fold("label", 5, Some(1)).unwrap() {
+        let labels = validation.to_dataset().unwrap().data.column("label").unwrap().str().unwrap().clone();
+        assert_eq!(labels.into_iter().filter(|l| *l == Some("attack")).count(), 1);
+    }
+    assert!(dataset.stratified_k_fold("label", 6, None).is_err());
+
+    assert_eq!(dataset.repeated_k_fold(4, 3, 7).unwrap().len(), 12);
+    assert_eq!(dataset.leave_one_group_out("host").unwrap().count(), 3);
+
+    let numeric = DataSet::new(dataset.data.select(["bytes"]).unwrap().with_row_index("target", None).unwrap());
+    let (train, _) = numeric.k_fold(4, None).unwrap().next().unwrap();
+    let (records, targets) = train.to_ndarray("target").unwrap();
+    assert_eq!(records.dim(), (15, 1));
+    assert_eq!(targets[0], 5.0);
+}
+
+#[test]
+fn test_group_split_keeps_hosts_apart() {
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+    use std::collections::HashSet;
+
+    // Host "a" is a heavy talker; the test set should approximate 25% of the rows, not of the hosts.
+    let hosts: Vec<&str> = (0..40).map(|i| if i < 20 { "a" } else { ["b", "c", "d", "e"][i % 4] }).collect();
+    let dataset = DataSet::new(df!("flow" => (0..40).collect::<Vec<i64>>(), "source_ip" => hosts).unwrap());
+    let hosts_of = |dataset: &DataSet| -> HashSet<String> {
+        let column = dataset.data.column("source_ip").unwrap().str().unwrap().clone();
+        column.into_iter().flatten().map(str::to_string).collect()
+    };
+
+    let splits = dataset.train_test_split_by_group("source_ip", 0.25, Some(3)).unwrap();
+    assert!(hosts_of(&splits["train"]).is_disjoint(&hosts_of(&splits["test"])));
+    assert_eq!(splits["test"].data.height(), 10);
+    assert_eq!(splits["test"].info.split.as_ref().unwrap().group_by.as_deref(), Some("source_ip"));
+
+    let folds: Vec<_> = dataset.group_k_fold("source_ip", 2).unwrap().collect();
+    assert_eq!(folds.iter().map(|(_, validation)| validation.len()).collect::<Vec<_>>(), vec![20, 20]);
+    for (train, validation) in &folds {
+        let train = hosts_of(&train.to_dataset().unwrap());
+        assert!(train.is_disjoint(&hosts_of(&validation.to_dataset().unwrap())));
+    }
+    assert!(dataset.group_k_fold("source_ip", 6).is_err());
+    assert!(dataset.group_k_fold("source_ip", 1).is_err());
+}
+
+#[test]
+fn test_time_split_and_rolling_origin() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::time_split::{parse_timestamp, RollingOriginOptions};
+    use polars::prelude::*;
+
+    // Records arrive out of order, one per day of January.
+    let days: Vec<i64> = (0..30).map(|i| (i * 7) % 30).collect();
+    let timestamps: Vec<String> = days.iter().map(|day| format!("2024-01-{:02} 12:00:00", day + 1)).collect();
+    let dataset = DataSet::new(df!("day" => &days, "timestamp" => timestamps).unwrap());
+
+    let splits = dataset.train_test_split_at("timestamp", parse_timestamp("2024-01-21").unwrap()).unwrap();
+    assert!(matches!(splits["train"].data.column("timestamp").unwrap().dtype(), DataType::Datetime(_, _)));
+    let train_days: Vec<i64> = splits["train"].data.column("day").unwrap().i64().unwrap().into_no_null_iter().collect();
+    assert_eq!(train_days, (0..20).collect::<Vec<i64>>());
+    assert_eq!(splits["test"].data.height(), 10);
+
+    // The last 20% of the 29-day span starts 5.8 days before January 30th at noon.
+    let splits = dataset.train_test_split_by_time("timestamp", 0.2).unwrap();
+    assert_eq!(splits["test"].data.height(), 6);
+
+    let options = RollingOriginOptions { n_splits: 3, test_size: Some(5), max_train_size: Some(10), gap: 2 };
+    for (train, validation) in dataset.rolling_origin("timestamp", &options).unwrap() {
+        let day = |row: &IdxSize| days[*row as usize];
+        assert_eq!(train.len(), 10);
+        assert_eq!(validation.rows().iter().map(day).min().unwrap(), train.rows().iter().map(day).max().unwrap() + 3);
+    }
+    assert!(dataset.train_test_split_at("timestamp", parse_timestamp("2023-12-31").unwrap()).is_err());
+}
+
+#[test]
+fn test_three_way_split() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::split::{parse_split_sizes, SplitOptions, SplitSize};
+    use polars::prelude::*;
+
+    let labels: Vec<&str> = (0..100).map(|i| if i % 5 == 0 { "attack" } else { "benign" }).collect();
+    let hosts: Vec<String> = (0..100).map(|i| format!("10.0.0.{}", i % 20)).collect();
+    let dataset = DataSet::new(df!("flow" => (0..100).collect::<Vec<i64>>(), "label" => labels, "source_ip" => hosts).unwrap());
+
+    let sizes = parse_split_sizes("train=0.7,validation=0.15,test=0.15").unwrap();
+    assert_eq!(sizes[1], ("validation".to_string(), SplitSize::Ratio(0.15)));
+    let splits = dataset.split(&sizes, &SplitOptions { seed: Some(42), ..Default::default() }).unwrap();
+    assert_eq!(splits.split_names(), vec!["train", "validation", "test"]);
+    assert_eq!(splits.num_rows(), 100);
+    assert_eq!(splits["test"].data.height(), 15);
+
+    // Counts and ratios mix; rows past the last split are left out.
+    let sizes = parse_split_sizes("train=0.5,test=10").unwrap();
+    let options = SplitOptions { stratify_by: Some("label".to_string()), ..Default::default() };
+    let splits = dataset.split(&sizes, &options).unwrap();
+    let attacks = splits["test"].data.column("label").unwrap().str().unwrap().into_iter().filter(|l| *l == Some("attack")).count();
+    assert_eq!((splits["train"].data.height(), splits["test"].data.height(), attacks), (50, 10, 2));
+
+    let options = SplitOptions { group_by: Some("source_ip".to_string()), seed: Some(1), ..Default::default() };
+    let splits = dataset.split(&parse_split_sizes("train=0.6,validation=0.2,test=0.2").unwrap(), &options).unwrap();
+    assert_eq!(splits.num_rows(), 100);
+    assert!(splits.iter().all(|(_, split)| split.data.height() % 5 == 0 && split.data.height() > 0));
+
+    assert!(dataset.split(&parse_split_sizes("train=0.9,test=0.2").unwrap(), &SplitOptions::default()).is_err());
+}
+
+#[test]
+fn test_split_expressions() {
+    use datasets_rs::dataset_dict::DatasetDict;
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::split_expr::{Bound, Rounding, SplitExpr};
+    use polars::prelude::*;
+
+    let mut splits = DatasetDict::new();
+    splits.insert("train", DataSet::new(df!("flow" => (0..200).collect::<Vec<i64>>()).unwrap()));
+    splits.insert("test", DataSet::new(df!("flow" => (1000..1050).collect::<Vec<i64>>()).unwrap()));
+    let flows = |expr: &str| -> Vec<i64> {
+        let dataset = splits.select_split(expr).unwrap();
+        dataset.data.column("flow").unwrap().i64().unwrap().into_no_null_iter().collect()
+    };
+
+    assert_eq!(flows("train[:10%]"), (0..20).collect::<Vec<i64>>());
+    assert_eq!(flows("train[10:13]"), vec![10, 11, 12]);
+    assert_eq!(flows("train[-2:]"), vec![198, 199]);
+    assert_eq!(flows("train[:2] + test[-10%:]"), vec![0, 1, 1045, 1046, 1047, 1048, 1049]);
+    assert_eq!(flows("test[:33%]").len(), 16);
+
+    let expr: SplitExpr = "train[:33%](pct1_dropremainder)".parse().unwrap();
+    assert_eq!(expr.slices[0].to, Some(Bound::Percent(33)));
+    assert_eq!(expr.slices[0].rounding, Rounding::Pct1DropRemainder);
+    assert_eq!(expr.apply(&splits).unwrap().data.height(), 66);
+    assert!(splits.select_split("test[:33%](pct1_dropremainder)").is_err());
+
+    // Splits whose dtypes differ are combined under their supertype.
+    let mut mixed = DatasetDict::new();
+    mixed.insert("train", DataSet::new(df!("flow" => [1i32, 2]).unwrap()));
+    mixed.insert("test", DataSet::new(df!("flow" => [3i64], "label" => ["attack"]).unwrap()));
+    let combined = mixed.select_split("train+test").unwrap();
+    assert_eq!(combined.data.column("flow").unwrap().dtype(), &DataType::Int64);
+    assert_eq!(combined.data.column("label").unwrap().null_count(), 2);
+
+    let error = SplitExpr::parse("train[:10%").unwrap_err().to_string();
+    assert!(error.contains("position 10") && error.contains("']'"), "{}", error);
+    assert!(SplitExpr::parse("train[:10%](nearest)").unwrap_err().to_string().contains("unknown rounding"));
+    assert!(SplitExpr::parse("train[10:20%]").is_err());
+    assert!(SplitExpr::parse("train[:150%]").is_err());
+    let error = splits.select_split("validation").err().unwrap().to_string();
+    assert!(error.contains("available splits: train, test"), "{}", error);
+}
+
+#[test]
+fn test_shards_and_manifest() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::shard::ShardManifest;
+    use polars::prelude::*;
+
+    let dataset = DataSet::new(df!("flow" => (0..10).collect::<Vec<i64>>()).unwrap());
+    let flows = |dataset: &DataSet| -> Vec<i64> { dataset.data.column("flow").unwrap().i64().unwrap().into_no_null_iter().collect() };
+
+    // As in Hugging Face, the first 10 % 3 shards get the extra rows.
+    assert_eq!(flows(&dataset.shard(3, 0, true).unwrap()), vec![0, 1, 2, 3]);
+    assert_eq!(flows(&dataset.shard(3, 2, true).unwrap()), vec![7, 8, 9]);
+    assert_eq!(flows(&dataset.shard(3, 1, false).unwrap()), vec![1, 4, 7]);
+    assert!(dataset.shard(3, 3, true).is_err());
+
+    let dir = TempDir::new("shards");
+    let shuffled = dataset.shuffle(5).unwrap();
+    let manifest = shuffled.save_shards(dir.path(), 3, true, "parquet").unwrap();
+    assert_eq!(ShardManifest::load(dir.path()).unwrap(), manifest);
+    assert_eq!(manifest.seed, Some(5));
+    assert_eq!(manifest.shards.iter().map(|shard| shard.num_rows).sum::<usize>(), 10);
+
+    let shard = DataSet::load_shard(dir.path(), 1).unwrap();
+    assert_eq!(flows(&shard), flows(&shuffled.shard(3, 1, true).unwrap()));
+    std::fs::write(dir.join(&manifest.shards[2].filename), b"tampered").unwrap();
+    assert!(DataSet::load_shard(dir.path(), 2).is_err());
+}
+
+#[test]
+fn test_concatenate_and_interleave() {
+    use datasets_rs::interleave::{concatenate_datasets, interleave_batches, interleave_datasets, InterleaveOptions, StoppingStrategy};
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+    use std::error::Error;
+
+    let firewall = DataSet::new(df!("id" => [0i64, 1, 2], "action" => ["allow", "deny", "allow"]).unwrap());
+    let ids = DataSet::new(df!("id" => [10i32, 11, 12, 13, 14], "alert" => ["scan", "scan", "dos", "dos", "scan"]).unwrap());
+    let ids_of = |dataset: &DataFrame| -> Vec<i64> { dataset.column("id").unwrap().i64().unwrap().into_no_null_iter().collect() };
+
+    let all = concatenate_datasets(&[firewall.clone(), ids.clone()]).unwrap();
+    assert_eq!(all.data.height(), 8);
+    assert_eq!(all.data.column("alert").unwrap().null_count(), 3);
+
+    // The result keeps the first dataset's info and is fingerprinted from every input.
+    let (mut fingerprinted, mut alerts) = (firewall.clone(), ids.clone());
+    fingerprinted.info.description = "Firewall log".to_string();
+    fingerprinted.fingerprint = Some("firewall".to_string());
+    let concatenated = concatenate_datasets(&[fingerprinted.clone(), alerts.clone()]).unwrap();
+    assert_eq!(concatenated.info.description, "Firewall log");
+    assert_eq!(concatenated.uuid, fingerprinted.uuid);
+    assert_eq!(concatenated.fingerprint, None);
+    alerts.fingerprint = Some("ids".to_string());
+    let concatenated = concatenate_datasets(&[fingerprinted.clone(), alerts.clone()]).unwrap();
+    let fingerprint = concatenated.fingerprint.clone().unwrap();
+    assert_eq!(concatenate_datasets(&[fingerprinted.clone(), alerts.clone()]).unwrap().fingerprint, Some(fingerprint.clone()));
+    assert_ne!(concatenate_datasets(&[alerts.clone(), fingerprinted.clone()]).unwrap().fingerprint, Some(fingerprint));
+
+    // Interleaving does the same, and its options change the fingerprint.
+    let pair = [fingerprinted.clone(), alerts];
+    let interleaved = interleave_datasets(&pair, &InterleaveOptions::default()).unwrap();
+    assert_eq!(interleaved.info.description, "Firewall log");
+    assert_eq!(interleaved.uuid, fingerprinted.uuid);
+    assert!(interleaved.fingerprint.is_some());
+    assert_eq!(interleaved.fingerprint, interleave_datasets(&pair, &InterleaveOptions::default()).unwrap().fingerprint);
+    let weighted = InterleaveOptions { probabilities: Some(vec![0.2, 0.8]), seed: 42, ..Default::default() };
+    assert_ne!(interleave_datasets(&pair, &weighted).unwrap().fingerprint, interleaved.fingerprint);
+
+    let sources = [firewall.clone(), ids.clone()];
+    let mixed = interleave_datasets(&sources, &InterleaveOptions::default()).unwrap();
+    assert_eq!(ids_of(&mixed.data), vec![0, 10, 1, 11, 2, 12]);
+    let options = InterleaveOptions { stopping_strategy: StoppingStrategy::AllExhausted, ..Default::default() };
+    let oversampled = interleave_datasets(&sources, &options).unwrap();
+    assert_eq!(ids_of(&oversampled.data), vec![0, 10, 1, 11, 2, 12, 0, 13, 1, 14]);
+
+    let options = InterleaveOptions { probabilities: Some(vec![0.2, 0.8]), seed: 42, ..Default::default() };
+    let sampled = interleave_datasets(&sources, &options).unwrap();
+    assert!(sampled.data.equals_missing(&interleave_datasets(&sources, &options).unwrap().data));
+    assert_eq!(sampled.info.seed, Some(42));
+
+    // Streamed sources give the same rows as their in-memory counterparts.
+    let batches = |dataset: &DataSet| -> std::vec::IntoIter<Result<DataFrame, Box<dyn Error>>> {
+        (0..dataset.data.height()).step_by(2).map(|start| Ok(dataset.data.slice(start as i64, 2))).collect::<Vec<_>>().into_iter()
+    };
+    for options in [InterleaveOptions::default(), InterleaveOptions { stopping_strategy: StoppingStrategy::AllExhausted, ..Default::default() }] {
+        let streamed: Vec<DataFrame> = interleave_batches(vec![batches(&firewall), batches(&ids)], &options, 4).unwrap().map(Result::unwrap).collect();
+        assert!(streamed.iter().all(|batch| batch.height() <= 4));
+        let streamed: Vec<i64> = streamed.iter().flat_map(&ids_of).collect();
+        assert_eq!(streamed, ids_of(&interleave_datasets(&sources, &options).unwrap().data));
+    }
+}
+
+#[test]
+fn test_parallel_map() {
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::map::{MapOptions, Row};
+    use polars::prelude::*;
+
+    let n = 2500;
+    let ports: Vec<i64> = (0..n).map(|i| if i % 3 == 0 { 443 } else { 8000 + i }).collect();
+    let protocols: Vec<&str> = (0..n).map(|i| if i % 2 == 0 { "tcp" } else { "udp" }).collect();
+    let dataset = DataSet::new(df!("dst_port" => ports.clone(), "protocol" => protocols).unwrap());
+
+    let remove_columns = vec!["protocol".to_string()];
+    let options = MapOptions { batch_size: 300, num_workers: 4, remove_columns, ..Default::default() };
+    let mapped = dataset
+        .map(
+            |row| {
+                let port = row.get("dst_port").and_then(|port| port.extract::<i64>()).unwrap_or_default();
+                row.set("well_known", port < 1024);
+                let protocol = row.get_str("protocol").unwrap_or_default().to_uppercase();
+                row.set_str("proto", &protocol);
+                Ok(())
+            },
+            &options,
+        )
+        .unwrap();
+    assert_eq!(mapped.uuid, dataset.uuid);
+    assert_eq!(mapped.data.get_column_names(), vec!["dst_port", "well_known", "proto"]);
+    let kept: Vec<i64> = mapped.data.column("dst_port").unwrap().i64().unwrap().into_no_null_iter().collect();
+    assert_eq!(kept, ports);
+    assert_eq!(mapped.data.column("well_known").unwrap().bool().unwrap().sum(), Some(834));
+    assert_eq!(mapped.data.column("proto").unwrap().str().unwrap().get(1), Some("UDP"));
+
+    // Batches can replace columns and change the number of rows.
+    let options = MapOptions { batch_size: 1000, ..Default::default() };
+    let filtered = dataset
+        .map_batches(
+            |batch| Ok(batch.lazy().filter(col("dst_port").eq(lit(443))).with_column(col("dst_port") * lit(2)).collect()?),
+            &options,
+        )
+        .unwrap();
+    assert_eq!(filtered.data.height(), 834);
+    assert!(filtered.data.column("dst_port").unwrap().i64().unwrap().into_no_null_iter().all(|port| port == 886));
+
+    let failed = dataset.map(|row| match row.get("dst_port").and_then(|port| port.extract::<i64>()) {
+        Some(8002) => Err("bad port".into()),
+        _ => Ok(()),
+    }, &MapOptions::default());
+    assert_eq!(failed.err().unwrap().to_string(), "Row 2: bad port");
+
+    // Untouched columns keep their dtype; a column removed from some rows is null in those rows.
+    let small = DataSet::new(df!("port" => [22u16, 80, 443], "flag" => ["a", "b", "c"], "drop" => [1i8, 2, 3]).unwrap());
+    let changed = small
+        .map(
+            |row| {
+                row.remove("drop");
+                if row.get_str("flag") == Some("b") {
+                    assert_eq!(row.remove("flag").unwrap().get_str(), Some("b"));
+                }
+                row.set_str("tag", &format!("{}!", row.get("port").unwrap()));
+                assert_eq!(row.columns().last(), Some("tag"));
+                Ok(())
+            },
+            &MapOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(changed.data.get_column_names(), vec!["port", "flag", "tag"]);
+    assert_eq!(changed.data.column("port").unwrap().dtype(), &DataType::UInt16);
+    assert_eq!(changed.data.column("flag").unwrap().null_count(), 1);
+    assert_eq!(changed.data.column("tag").unwrap().str().unwrap().get(2), Some("443!"));
+    // Whether a column is dropped does not depend on how the rows are batched.
+    let remove_flag_below_443 = |row: &mut Row| {
+        if row.get("port").and_then(|port| port.extract::<u16>()) != Some(443) {
+            row.remove("flag");
+        }
+        Ok(())
+    };
+    let by_one = small.map(remove_flag_below_443, &MapOptions { batch_size: 1, ..Default::default() }).unwrap();
+    let by_two = small.map(remove_flag_below_443, &MapOptions { batch_size: 2, ..Default::default() }).unwrap();
+    assert!(by_one.data.equals_missing(&small.map(remove_flag_below_443, &MapOptions::default()).unwrap().data));
+    assert!(by_two.data.equals_missing(&by_one.data));
+    assert_eq!(by_one.data.get_column_names(), vec!["port", "flag", "drop"]);
+    assert_eq!(by_one.data.column("flag").unwrap().null_count(), 2);
+    let remove_all = |row: &mut Row| {
+        row.remove("drop");
+        Ok(())
+    };
+    let by_two = small.map(remove_all, &MapOptions { batch_size: 2, ..Default::default() }).unwrap();
+    assert_eq!(by_two.data.get_column_names(), vec!["port", "flag"]);
+    let options = MapOptions { remove_columns: vec!["missing".to_string()], ..Default::default() };
+    assert!(dataset.map_batches(Ok, &options).is_err());
+}
+
+#[test]
+fn test_row_selection_and_views() {
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let data = df!(
+        "host" => ["web1", "db1", "web1", "web2", "db1", "web2"],
+        "bytes" => [Some(500i64), Some(20), None, Some(7000), Some(20), Some(90)],
+        "label" => ["benign", "benign", "attack", "attack", "benign", "benign"]
+    )
+    .unwrap();
+    let dataset = DataSet::new(data);
+    let hosts = |dataset: &DataSet| -> Vec<String> {
+        dataset.data.column("host").unwrap().str().unwrap().into_no_null_iter().map(str::to_string).collect()
+    };
+
+    let large = dataset.filter(col("bytes").gt(lit(100))).unwrap();
+    assert_eq!(hosts(&large), vec!["web1", "web2"]);
+    assert_eq!((large.uuid, &large.timestamp), (dataset.uuid, &dataset.timestamp));
+    let attacks = dataset.filter_rows(|row| row.get_str("label") == Some("attack")).unwrap();
+    assert_eq!(hosts(&attacks), vec!["web1", "web2"]);
+
+    assert_eq!(hosts(&dataset.select(&[3, 0, 3]).unwrap()), vec!["web2", "web1", "web2"]);
+    assert!(dataset.select(&[6]).is_err());
+    assert_eq!(dataset.take(2).data.height(), 2);
+    assert_eq!(dataset.take(10).data.height(), 6);
+
+    let sorted = dataset.sort(&["bytes"], true).unwrap();
+    assert_eq!(hosts(&sorted), vec!["web2", "web1", "web2", "db1", "db1", "web1"]);
+    let sorted = dataset.sort(&["host", "bytes"], false).unwrap();
+    assert_eq!(hosts(&sorted), vec!["db1", "db1", "web1", "web1", "web2", "web2"]);
+    assert_eq!(dataset.unique(None).unwrap().data.height(), 5);
+    assert_eq!(hosts(&dataset.unique(Some(&["host"])).unwrap()), vec!["web1", "db1", "web2"]);
+
+    let sampled = dataset.sample_frac(0.5, 7).unwrap();
+    assert_eq!(sampled.data.height(), 3);
+    assert_eq!(sampled.info.seed, Some(7));
+    assert!(sampled.data.equals_missing(&dataset.sample(3, 7).unwrap().data));
+    assert!(dataset.sample_frac(1.5, 7).is_err());
+
+    // Chained view operations only compose row indices until the rows are materialized.
+    let view = dataset.view().filter(col("label").eq(lit("benign"))).unwrap().sort(&["bytes"], false).unwrap().take(3);
+    assert_eq!(view.rows(), &[1, 4, 5]);
+    let view = view.unique(Some(&["host"])).unwrap();
+    assert_eq!(view.rows(), &[1, 5]);
+    let dir = TempDir::new("view");
+    view.save_data(dir.join("benign.csv"), "csv").unwrap();
+    let saved = DataSet::load_data(dir.join("benign.csv")).unwrap();
+    assert_eq!(hosts(&DataSet::new(saved)), vec!["db1", "web2"]);
+}
+
+#[test]
+fn test_fingerprints_and_transform_cache() {
+    use datasets_rs::cache;
+
+    // The cache directory is set for this thread only, as other tests run in parallel.
+    let dir = TempDir::new("cache");
+    cache::with_cache_dir(dir.join("cache"), || check_fingerprints_and_transform_cache(&dir));
+    assert_ne!(cache::cache_dir(), dir.join("cache"));
+}
+
+fn check_fingerprints_and_transform_cache(dir: &TempDir) {
+    use datasets_rs::cache;
+    use datasets_rs::csv_options::CsvOptions;
+    use datasets_rs::hf_disk;
+    use datasets_rs::load_dataset::DataSet;
+    use datasets_rs::map::MapOptions;
+    use polars::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let input = dir.join("flows.csv");
+    std::fs::write(&input, "src_port,dst_port,label\n5000,443,benign\n5001,22,attack\n5002,80,benign\n").unwrap();
+    let load = || datasets_rs::load_dataset(input.to_str().unwrap(), None, &CsvOptions::default()).unwrap()["train"].clone();
+
+    let flows = load();
+    assert!(flows.fingerprint.is_some());
+    assert_eq!(flows.fingerprint, load().fingerprint);
+    let options = CsvOptions { null_values: vec!["-".to_string()], ..Default::default() };
+    let with_nulls = datasets_rs::load_dataset(input.to_str().unwrap(), None, &options).unwrap()["train"].clone();
+    assert_ne!(with_nulls.fingerprint, flows.fingerprint);
+    let in_memory = DataSet::new(flows.data.clone());
+    assert_eq!(in_memory.fingerprint, None);
+    assert_eq!(in_memory.fingerprint().unwrap(), DataSet::new(flows.data.clone()).fingerprint().unwrap());
+
+    // Each transform derives a new fingerprint from its input and parameters.
+    let sorted = flows.sort(&["dst_port"], false).unwrap();
+    assert_ne!(sorted.fingerprint, flows.fingerprint);
+    assert_eq!(sorted.fingerprint, flows.sort(&["dst_port"], false).unwrap().fingerprint);
+    assert_ne!(sorted.fingerprint, flows.sort(&["dst_port"], true).unwrap().fingerprint);
+    assert!(dir.join("cache").join(format!("{}.arrow", sorted.fingerprint.as_ref().unwrap())).exists());
+    let shuffled = load().shuffle(3).unwrap();
+    assert_eq!(shuffled.info.seed, Some(3));
+    assert!(shuffled.data.equals(&flows.shuffle(3).unwrap().data));
+    let shard = flows.shard(2, 1, true).unwrap();
+    assert!(shard.fingerprint.is_some());
+    assert_eq!(shard.fingerprint, flows.shard(2, 1, true).unwrap().fingerprint);
+    assert_ne!(shard.fingerprint, flows.shard(2, 1, false).unwrap().fingerprint);
+    // Expressions have no stable serialization, so filter results are not fingerprinted.
+    assert_eq!(flows.filter(col("dst_port").lt(lit(100))).unwrap().fingerprint, None);
+
+    // A keyed map runs its closure only until its result is cached.
+    let calls = AtomicUsize::new(0);
+    let options = MapOptions { cache_key: Some("well_known-v1".to_string()), ..Default::default() };
+    let well_known = |dataset: &DataSet| {
+        dataset
+            .map(
+                |row| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let port = row.get("dst_port").and_then(|port| port.extract::<i64>()).unwrap_or_default();
+                    row.set("well_known", port < 1024);
+                    Ok(())
+                },
+                &options,
+            )
+            .unwrap()
+    };
+    let mapped = well_known(&flows);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    let reused = well_known(&load());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert!(reused.data.equals(&mapped.data));
+    assert_eq!(reused.fingerprint, mapped.fingerprint);
+    cache::without_caching(|| well_known(&flows));
+    assert_eq!(calls.load(Ordering::SeqCst), 6);
+
+    // Changing the source file changes every fingerprint derived from it.
+    std::fs::write(&input, "src_port,dst_port,label\n5000,443,benign\n").unwrap();
+    let changed = load();
+    assert_ne!(changed.fingerprint, flows.fingerprint);
+    assert_eq!(well_known(&changed).data.height(), 1);
+
+    // The fingerprint survives save_to_disk.
+    hf_disk::save_dataset_dir(&sorted, dir.join("sorted"), 1).unwrap();
+    assert_eq!(hf_disk::load_dataset_dir(dir.join("sorted")).unwrap().fingerprint, sorted.fingerprint);
+
+    // Clearing the cache leaves files that are not cache entries alone.
+    std::fs::write(dir.join("cache").join("notes.json"), "{}").unwrap();
+    assert!(cache::clear_cache().unwrap() >= 3);
+    let left: Vec<_> = std::fs::read_dir(dir.join("cache")).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(left, vec!["notes.json"]);
+}
+
+#[test]
+fn test_column_management() {
+    use datasets_rs::columns::parse_assignment;
+    use datasets_rs::load_dataset::DataSet;
+    use polars::prelude::*;
+
+    let data = df!("src" => ["10.0.0.1", "10.0.0.2"], "dst_port" => ["443", "22"], "label" => [0i64, 1]).unwrap();
+    let dataset = DataSet::new(data);
+    let names = |dataset: &DataSet| -> Vec<String> { dataset.data.get_column_names().iter().map(|n| n.to_string()).collect() };
+
+    let renamed = dataset.rename_columns(&[("src", "source_ip"), ("label", "target")]).unwrap();
+    assert_eq!(names(&renamed), vec!["source_ip", "dst_port", "target"]);
+    assert_eq!(renamed.uuid, dataset.uuid);
+    assert!(dataset.rename_column("src", "label").is_err());
+    let missing = dataset.rename_column("dst", "port").err().unwrap().to_string();
+    assert_eq!(missing, "Column dst not found; available columns: src, dst_port, label");
+
+    assert_eq!(names(&dataset.remove_columns(&["src"]).unwrap()), vec!["dst_port", "label"]);
+    assert!(dataset.remove_columns(&["src", "nope"]).is_err());
+    assert_eq!(names(&dataset.select_columns(&["label", "src"]).unwrap()), vec!["label", "src"]);
+
+    let cast = dataset.cast_column("dst_port", &DataType::UInt16).unwrap();
+    assert_eq!(cast.data.column("dst_port").unwrap().dtype(), &DataType::UInt16);
+    assert!(dataset.cast_column("src", &DataType::Int32).is_err());
+
+    let added = dataset.add_column("sensor", Series::new("", ["fw01", "fw01"])).unwrap();
+    assert_eq!(names(&added), vec!["src", "dst_port", "label", "sensor"]);
+    assert!(dataset.add_column("label", Series::new("", [1i64, 2])).is_err());
+    assert!(dataset.add_column("sensor", Series::new("", ["fw01"])).is_err());
+    assert_eq!(parse_assignment("sensor=fw01").unwrap(), ("sensor".to_string(), "fw01".to_string()));
+    assert!(parse_assignment("=fw01").is_err());
+
+    let ports = [Series::new("src_port", [5000i64, 5001]), Series::new("dst_port", [443i64, 22])];
+    let ports = StructChunked::from_series("conn", &ports).unwrap().into_series();
+    let endpoint = [Series::new("ip", ["10.0.0.1", "10.0.0.2"]), ports];
+    let endpoint = StructChunked::from_series("endpoint", &endpoint).unwrap();
+    let nested = DataSet::new(DataFrame::new(vec![endpoint.into_series(), Series::new("label", [0i64, 1])]).unwrap());
+    let flat = nested.flatten().unwrap();
+    assert_eq!(names(&flat), vec!["endpoint.ip", "endpoint.conn.src_port", "endpoint.conn.dst_port", "label"]);
+    assert_eq!(flat.data.column("endpoint.conn.dst_port").unwrap().i64().unwrap().get(1), Some(22));
+}
+
+}
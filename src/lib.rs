@@ -1,5 +1,11 @@
-use clap::{Command, Arg};
-use dataloader_rs::lib::{CSVSecurityDataset, JSONSecurityDataset, ParquetSecurityDataset};
+use clap::{ArgMatches, Command, Arg};
+use datasets_rs::cache;
+use datasets_rs::columns::{check_columns, parse_assignment};
+use datasets_rs::csv_options::{parse_char, parse_dtype, parse_dtype_override, CsvOptions, TextEncoding};
+use datasets_rs::load_dataset::DataSet;
+use datasets_rs::load_dataset_split;
+use datasets_rs::split::{parse_split_sizes, SplitOptions};
+use datasets_rs::time_split::parse_timestamp;
 use syn_crabs::setup_logging;
 use polars::prelude::*;
 use std::error::Error;
@@ -16,8 +22,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short('f')
                 .long("format")
                 .takes_value(true)
-                .possible_values(&["csv", "json", "parquet"])
-                .help("Specify the input dataset format (csv, json, parquet)"),
+                .possible_values(&["auto", "csv", "json", "jsonl", "parquet"])
+                .default_value("auto")
+                .help("Specify the input dataset format (auto, csv, json, jsonl, parquet); auto sniffs it from the content"),
         )
         .arg(
             Arg::new("input")
@@ -42,9 +49,217 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .default_value("0.2")
                 .help("Ratio of the test set (default: 0.2)"),
         )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for shuffling and splitting (default: random, logged so the run can be repeated)"),
+        )
+        .arg(
+            Arg::new("stratify")
+                .long("stratify")
+                .help("Keep the class proportions of the target column in the train and test sets"),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .takes_value(true)
+                .conflicts_with("stratify")
+                .help("Keep all rows with the same value of this column, e.g. source_ip, in one split"),
+        )
+        .arg(
+            Arg::new("time_column")
+                .long("time-column")
+                .takes_value(true)
+                .conflicts_with_all(&["stratify", "group"])
+                .help("Split chronologically on this timestamp column, testing on the last test_ratio of the time span"),
+        )
+        .arg(
+            Arg::new("cutoff")
+                .long("cutoff")
+                .takes_value(true)
+                .requires("time_column")
+                .help("With --time-column, test on the rows timestamped at or after this time, e.g. 2024-06-01"),
+        )
+        .arg(
+            Arg::new("split")
+                .long("split")
+                .takes_value(true)
+                .help("Load only the rows named by a split expression, e.g. train[:10%] or train[:50%]+test[50%:]"),
+        )
+        .arg(
+            Arg::new("splits")
+                .long("splits")
+                .takes_value(true)
+                .conflicts_with("time_column")
+                .help("Named split sizes as ratios or row counts, e.g. train=0.8,validation=0.1,test=0.1"),
+        )
+        .arg(
+            Arg::new("output_dir")
+                .long("output-dir")
+                .takes_value(true)
+                .help("Write each split to <output-dir>/<split>.<output-format>"),
+        )
+        .arg(
+            Arg::new("output_format")
+                .long("output-format")
+                .takes_value(true)
+                .default_value("csv")
+                .help("File format of the written splits, e.g. csv, parquet or jsonl.gz (default: csv)"),
+        )
+        .arg(
+            Arg::new("delimiter")
+                .long("delimiter")
+                .takes_value(true)
+                .help("CSV field delimiter, e.g. ';' or '\\t' (default: sniffed)"),
+        )
+        .arg(
+            Arg::new("quote")
+                .long("quote")
+                .takes_value(true)
+                .help("CSV quote character (default: '\"')"),
+        )
+        .arg(
+            Arg::new("escape")
+                .long("escape")
+                .takes_value(true)
+                .help("CSV escape character for quotes inside quoted fields, e.g. '\\'"),
+        )
+        .arg(
+            Arg::new("no_header")
+                .long("no-header")
+                .help("The CSV file has no header row; columns are named column_1, column_2, ..."),
+        )
+        .arg(
+            Arg::new("comment")
+                .long("comment")
+                .takes_value(true)
+                .help("Skip CSV lines starting with this prefix"),
+        )
+        .arg(
+            Arg::new("null")
+                .long("null")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Treat this CSV value as null; may be repeated"),
+        )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .takes_value(true)
+                .possible_values(&["utf8", "utf8-lossy", "latin1"])
+                .default_value("utf8")
+                .help("CSV text encoding"),
+        )
+        .arg(
+            Arg::new("skip_rows")
+                .long("skip-rows")
+                .takes_value(true)
+                .default_value("0")
+                .help("Number of CSV lines to skip before the header"),
+        )
+        .arg(
+            Arg::new("dtype")
+                .long("dtype")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Override a CSV column dtype, e.g. 'port=u16'; may be repeated"),
+        )
+        .arg(
+            Arg::new("flatten")
+                .long("flatten")
+                .help("Turn struct columns, e.g. from nested JSON, into one column per field named <column>.<field>"),
+        )
+        .arg(
+            Arg::new("rename")
+                .long("rename")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Rename a column, e.g. 'src=source_ip'; may be repeated"),
+        )
+        .arg(
+            Arg::new("cast")
+                .long("cast")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Cast a column after loading, e.g. 'dst_port=u16'; may be repeated"),
+        )
+        .arg(
+            Arg::new("add_column")
+                .long("add-column")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Add a column with the same string in every row, e.g. 'sensor=fw01'; may be repeated"),
+        )
+        .arg(
+            Arg::new("remove_column")
+                .long("remove-column")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Remove a column; may be repeated"),
+        )
+        .arg(
+            Arg::new("select_columns")
+                .long("select-columns")
+                .takes_value(true)
+                .help("Keep only these comma-separated columns, in this order, e.g. 'src_port,dst_port,target'"),
+        )
+        .arg(
+            Arg::new("no_cache")
+                .long("no-cache")
+                .help("Neither reuse nor write cached transform results"),
+        )
+        .subcommand(Command::new("clear-cache").about("Removes all cached transform results and exits"))
+        .subcommand(
+            Command::new("shard")
+                .about("Writes the dataset as shard files with a shards.json manifest, shuffled first if --seed is given")
+                .arg(
+                    Arg::new("num_shards")
+                        .long("num-shards")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Number of shard files to write"),
+                )
+                .arg(
+                    Arg::new("output_dir")
+                        .long("output-dir")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Directory for the shards and the manifest"),
+                )
+                .arg(
+                    Arg::new("interleaved")
+                        .long("interleaved")
+                        .help("Give shard i every num-shards-th row starting at row i, instead of a block of rows"),
+                ),
+        )
         .get_matches();
 
-    let (format, input_path, target_column, test_ratio): (&str, &str, &str, f32) = if matches.is_present("format") && matches.is_present("input") {
+    if matches.is_present("no_cache") {
+        cache::disable_caching();
+    }
+    if matches.subcommand_matches("clear-cache").is_some() {
+        cache::clear_cache()?;
+        return Ok(());
+    }
+
+    let csv_options = CsvOptions {
+        separator: matches.value_of("delimiter").map(parse_char).transpose()?,
+        quote_char: Some(matches.value_of("quote").map(parse_char).transpose()?.unwrap_or(b'"')),
+        escape_char: matches.value_of("escape").map(parse_char).transpose()?,
+        has_header: !matches.is_present("no_header"),
+        comment_prefix: matches.value_of("comment").map(str::to_string),
+        null_values: matches.values_of("null").map(|v| v.map(str::to_string).collect()).unwrap_or_default(),
+        encoding: TextEncoding::from_name(matches.value_of("encoding").unwrap())?,
+        skip_rows: matches.value_of("skip_rows").unwrap().parse()?,
+        dtypes: matches
+            .values_of("dtype")
+            .map(|v| v.map(parse_dtype_override).collect::<Result<_, _>>())
+            .transpose()?
+            .unwrap_or_default(),
+    };
+
+    let (format, input_path, target_column, test_ratio): (&str, &str, &str, f32) = if matches.is_present("input") {
         let format = matches.value_of("format").unwrap();
         let input_path = matches.value_of("input").unwrap();
         let target_column = matches.value_of("target_column").unwrap();
@@ -53,7 +268,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     } else {
         let input_path = "data/security_dataset.csv";
         let target_column = "target";
-        let format = "csv";
+        let format = "auto";
         let test_ratio: f32 = 0.2;
         (format, input_path, target_column, test_ratio)
     };
@@ -63,49 +278,104 @@ fn main() -> Result<(), Box<dyn Error>> {
     log::info!("Target column: {}", target_column);
     log::info!("Test ratio: {}", test_ratio);
 
-    let df = match format {
-        "csv" => CSVSecurityDataset::from_csv(input_path)?,
-        "json" => JSONSecurityDataset::from_json(input_path)?,
-        "parquet" => ParquetSecurityDataset::from_parquet(input_path)?,
-        _ => unreachable!(),
+    let df = match (matches.value_of("split"), format) {
+        (Some(split), _) => load_dataset_split(input_path, None, split, &csv_options)?.data,
+        (None, "auto") => DataSet::load_data_with(input_path, &csv_options)?,
+        (None, format) => DataSet::load_data_as(input_path, format, &csv_options)?,
     };
 
-    let mut shuffled_df = df.clone();
-    shuffled_df.shuffle();
-
-    let (X, y) = split_X_y(&shuffled_df, target_column)?;
+    let seed: u64 = match matches.value_of("seed") {
+        Some(seed) => seed.parse()?,
+        None => rand::random(),
+    };
+    log::info!("Seed: {}", seed);
 
-    let (X_train, X_test, y_train, y_test) = train_test_split(&X, &y, test_ratio)?;
+    let mut dataset = DataSet::new(df);
+    if matches.value_of("split").is_none() {
+        // Fingerprint the input so that transforms such as the shuffle below are cached.
+        dataset.fingerprint = cache::source_fingerprint(&[input_path], &csv_options)?;
+    }
+    let dataset = manage_columns(dataset, &matches)?;
+    if let Some(shard_matches) = matches.subcommand_matches("shard") {
+        let num_shards: usize = shard_matches.value_of("num_shards").unwrap().parse()?;
+        // Only shuffle with an explicit seed, so shards of an unshuffled input keep the file order.
+        let dataset = if matches.is_present("seed") { dataset.shuffle(seed)? } else { dataset };
+        dataset.save_shards(
+            shard_matches.value_of("output_dir").unwrap(),
+            num_shards,
+            !shard_matches.is_present("interleaved"),
+            matches.value_of("output_format").unwrap(),
+        )?;
+        return Ok(());
+    }
+    let splits = if let Some(sizes) = matches.value_of("splits") {
+        let options = SplitOptions {
+            seed: Some(seed),
+            stratify_by: matches.is_present("stratify").then(|| target_column.to_string()),
+            group_by: matches.value_of("group").map(str::to_string),
+        };
+        dataset.split(&parse_split_sizes(sizes)?, &options)?
+    } else if let Some(time_column) = matches.value_of("time_column") {
+        match matches.value_of("cutoff") {
+            Some(cutoff) => dataset.train_test_split_at(time_column, parse_timestamp(cutoff)?)?,
+            None => dataset.train_test_split_by_time(time_column, test_ratio)?,
+        }
+    } else if let Some(group_column) = matches.value_of("group") {
+        dataset.train_test_split_by_group(group_column, test_ratio, Some(seed))?
+    } else if matches.is_present("stratify") {
+        dataset.stratified_train_test_split(target_column, test_ratio, Some(seed))?
+    } else {
+        dataset.train_test_split(test_ratio, Some(seed))?
+    };
+    log::info!("{}", splits);
+    if let Some(output_dir) = matches.value_of("output_dir") {
+        let output_format = matches.value_of("output_format").unwrap();
+        splits.save_data(output_dir, output_format)?;
+        log::info!("Splits written to {}", output_dir);
+    }
 
-    log::info!("X_train shape: {} rows, {} cols", X_train.height(), X_train.width());
-    log::info!("y_train length: {}", y_train.len());
-    log::info!("X_test shape: {} rows, {} cols", X_test.height(), X_test.width());
-    log::info!("y_test length: {}", y_test.len());
+    for (name, split) in splits.iter() {
+        let (X, y) = split_X_y(&split.data, target_column)?;
+        log::info!("X_{} shape: {} rows, {} cols", name, X.height(), X.width());
+        log::info!("y_{} length: {}", name, y.len());
+    }
 
     Ok(())
 }
 
+/// Applies the column options in order: flatten, rename, cast, add, remove, then select.
+fn manage_columns(mut dataset: DataSet, matches: &ArgMatches) -> Result<DataSet, Box<dyn Error>> {
+    let values = |name: &str| -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        matches.values_of(name).map(|v| v.map(parse_assignment).collect()).unwrap_or_else(|| Ok(Vec::new()))
+    };
+    if matches.is_present("flatten") {
+        dataset = dataset.flatten()?;
+    }
+    let renames = values("rename")?;
+    if !renames.is_empty() {
+        let renames: Vec<(&str, &str)> = renames.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+        dataset = dataset.rename_columns(&renames)?;
+    }
+    for (column, dtype) in values("cast")? {
+        dataset = dataset.cast_column(&column, &parse_dtype(&dtype)?)?;
+    }
+    for (name, value) in values("add_column")? {
+        let height = dataset.data.height();
+        dataset = dataset.add_column(&name, Series::new(&name, vec![value.as_str(); height]))?;
+    }
+    if let Some(columns) = matches.values_of("remove_column") {
+        dataset = dataset.remove_columns(&columns.collect::<Vec<_>>())?;
+    }
+    if let Some(columns) = matches.value_of("select_columns") {
+        dataset = dataset.select_columns(&columns.split(',').map(str::trim).collect::<Vec<_>>())?;
+    }
+    Ok(dataset)
+}
+
 /// Splits the DataFrame into X (features) and y (target).
 fn split_X_y(df: &DataFrame, target_column: &str) -> Result<(DataFrame, Series), Box<dyn Error>> {
+    check_columns(df, &[target_column]).map_err(|e| format!("Invalid --target: {}", e))?;
     let y = df.column(target_column)?.clone();
     let X = df.drop(target_column)?;
     Ok((X, y))
 }
-
-/// Splits the dataset into training and testing sets.
-pub fn train_test_split(
-    X: &DataFrame,
-    y: &Series,
-    test_ratio: f32,
-) -> Result<(DataFrame, DataFrame, Series, Series), Box<dyn Error>> {
-    let n = X.height();
-    let test_size = (n as f32 * test_ratio).round() as usize;
-
-    let train_size = n - test_size;
-    let X_train = X.slice(0, train_size);
-    let X_test = X.slice(train_size, test_size);
-    let y_train = y.slice(0, train_size);
-    let y_test = y.slice(train_size, test_size);
-
-    Ok((X_train, X_test, y_train, y_test))
-}